@@ -0,0 +1,103 @@
+//! Minimal JSON serialisation for the `--json` output
+
+pub trait ToJson {
+    fn to_json(&self) -> String;
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> String {
+        let mut output = String::with_capacity(self.len() + 2);
+        output.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+                c => output.push(c),
+            }
+        }
+        output.push('"');
+        output
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> String {
+        self.as_str().to_json()
+    }
+}
+
+impl ToJson for usize {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToJson for u128 {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToJson for bool {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToJson for f64 {
+    fn to_json(&self) -> String {
+        if self.is_finite() {
+            self.to_string()
+        } else {
+            "null".to_string()
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> String {
+        match self {
+            Some(value) => value.to_json(),
+            None => "null".to_string(),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn to_json(&self) -> String {
+        format!("[{}]", self.iter().map(|value| value.to_json()).collect::<Vec<_>>().join(","))
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> String {
+        self.as_slice().to_json()
+    }
+}
+
+/// JSON object built up one field at a time, keeping the insertion order
+#[derive(Debug, Clone, Default)]
+pub struct JsonObject {
+    fields: Vec<(String, String)>,
+}
+
+impl JsonObject {
+    pub fn new() -> JsonObject {
+        JsonObject::default()
+    }
+
+    pub fn field<T: ToJson + ?Sized>(mut self, name: &str, value: &T) -> JsonObject {
+        self.fields.push((name.to_json(), value.to_json()));
+        self
+    }
+}
+
+impl ToJson for JsonObject {
+    fn to_json(&self) -> String {
+        format!("{{{}}}", self.fields.iter().map(|(name, value)| format!("{name}:{value}")).collect::<Vec<_>>().join(","))
+    }
+}
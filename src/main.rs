@@ -1,458 +1,4536 @@
-use std::collections::{HashSet, VecDeque};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Display, Formatter};
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use std::fs;
+use std::io::{self, IsTerminal};
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
 use itertools::Itertools;
+use json::{JsonObject, ToJson};
+use rules::DeductionRule;
+
+mod json;
+mod rules;
 
 #[derive(Parser, Debug)]
-#[command(about = "Formatting: '-' uncovered, '?' covered, 'X' known bomb, [1 - 9] numbers")]
+#[command(about = "Formatting: '-' uncovered, '?' covered, 'F' flagged, 'X' known bomb, [1 - 9] numbers. \
+    Run with --example for a sample board to pipe back in", subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)]
-    file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, help = "File to read the board from. Without it the board is read from stdin \
+        when it's piped in and not empty, or else from the MINESWEEPER_BOARD environment variable")]
+    file: Option<PathBuf>,
+    #[arg(long, value_name = "CELLS", allow_hyphen_values = true, requires = "width",
+        conflicts_with_all = ["file", "auto_legend", "lenient", "ints", "ragged"], help = "Reads the board from a \
+        single string of cell characters, row after row, instead of a file")]
+    flat: Option<String>,
+    #[arg(long, value_name = "N", requires = "flat", help = "Number of cells in each row of --flat")]
+    width: Option<usize>,
+    #[arg(long, conflicts_with_all = ["auto_legend", "lenient", "ints", "ragged"], help = "Reads the input as a \
+        'dimensions W H' line followed by an 'X,Y,CELL' line for each known cell, such as '3,4,2' or '0,0,X'. \
+        Every cell not listed is covered")]
+    sparse: bool,
     #[arg(short, long, help = "Shows individual board possibilities instead of only the guaranteed cells")]
     show_possibilities: bool,
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_rect, conflicts_with = "crop_with_border",
+        help = "Only solve the given sub-rectangle of the board. Numbers outside the crop are dropped, so \
+        cells on the crop's edge lose any constraints those numbers placed on them")]
+    crop: Option<(usize, usize, usize, usize)>,
+    #[arg(long, value_name = "X,Y,W,H", value_parser = parse_rect,
+        help = "Like --crop but also keeps one ring of surrounding cells so numbers bordering the crop still \
+        constrain it. Numbers in that ring may themselves touch cells outside of it. Numbers inside the crop \
+        are only fully constrained when that border is kept")]
+    crop_with_border: Option<(usize, usize, usize, usize)>,
+    #[arg(long, help = "Treats the board as part of a larger one, so numbers on its edge may count mines beyond \
+        it. A number N with K neighbours past the edge then only needs between N - K and N bombs in view, as if \
+        written '(N-K - N)'. Stops numbers on the edge of a --crop contradicting the cells around them")]
+    open_border: bool,
+    #[arg(long, help = "Prints the result as a single JSON object instead of text. Lists of cells are in row major \
+        order unless they're ranked, so the output of two runs can be diffed. A number written as a range has \
+        a value of {\"min\": N, \"max\": K}")]
+    json: bool,
+    #[arg(long, help = "Reports cells that are a bomb in every live search branch as soon as they're found, \
+        before the search finishes")]
+    report_forced: bool,
+    #[arg(short, long, value_name = "N", value_parser = parse_mine_range, help = "Total number of mines on the \
+        board, either exactly N or a range - A..B for at least A and fewer than B, A..=B for at least A and at \
+        most B. Every covered cell can then hold a bomb, so the search has to consider cells that don't touch \
+        any number and may be much slower")]
+    mines: Option<(usize, usize)>,
+    #[arg(long, value_name = "X,Y,W,H=N", value_parser = parse_mine_count,
+        help = "Number of mines inside the given rectangle, or a range of them as for --mines. Can be repeated \
+        - where regions overlap, both counts have to hold for the cells they share")]
+    region_mines: Vec<MineCount>,
+    #[arg(long, value_name = "X,Y;...", value_parser = parse_pair, value_delimiter = ';', help = "Cells known to be \
+        mines, as semicolon separated coordinates, placed as bombs before solving")]
+    mine_cells: Vec<(usize, usize)>,
+    #[arg(long, help = "Guesses which symbols the input uses for covered cells ('?' or '#'), bombs ('X' or '*') \
+        and empty cells ('-', '.', '_' or '0'), falling back to the default symbols if that's unclear")]
+    auto_legend: bool,
+    #[arg(long, help = "Prints the overall mine density and the expected density of each region of covered cells \
+        that share numbers")]
+    density: bool,
+    #[arg(long, help = "Prints how many bomb layouts each region of covered cells that share numbers has, and \
+        the range of bombs in it")]
+    region_report: bool,
+    #[arg(long, help = "Prints each region of covered cells that share numbers as its own part of the \
+        guaranteed cells, with the status and chance of being a bomb of each cell in it")]
+    dump_regions: bool,
+    #[arg(long, help = "Prints a heatmap of each covered cell's chance of being a bomb, coloured from green to \
+        red on terminals with truecolor support (COLORTERM=truecolor or 24bit) and drawn with the ramp \
+        '.:;+=*%#' from safest to most dangerous otherwise")]
+    heatmap: bool,
+    #[arg(long, help = "Prints how many possibilities have each total number of mines")]
+    mine_histogram: bool,
+    #[arg(long, requires = "heatmap", help = "Writes each --heatmap cell's chance of being a bomb as a \
+        percentage over its colour")]
+    heatmap_percent: bool,
+    #[arg(long, value_name = "FILE", help = "Writes the numbers and covered cells left after static analysis to \
+        FILE as a Graphviz graph, with an edge from each number to the covered cells it constrains and a colour \
+        for each region")]
+    export_dot: Option<PathBuf>,
+    #[arg(long, help = "Only lists the coordinates of every covered cell with whether it's a bomb, safe or \
+        ambiguous and its chance of being a bomb")]
+    list_covered: bool,
+    #[arg(long, help = "Only lists every covered cell next to a number with its chance of being a bomb, \
+        safest first")]
+    rank_moves: bool,
+    #[arg(long, conflicts_with_all = ["move_only", "status_line", "list_covered", "apply"], help = "Only prints \
+        the guaranteed bombs to flag and guaranteed safe cells to click as 'flag:' and 'click:' lines of \
+        coordinates, and a 'guess:' line with the best guess when neither has any cells")]
+    actions: bool,
+    #[arg(long, conflicts_with = "mines_touch_numbers", help = "Only lists the cells static analysis proves safe \
+        or bombs as 'safe X Y' and 'bomb X Y' lines, without searching. Much faster than solving but may miss \
+        cells only the search decides. Includes --trial-deduction if given")]
+    forced_only: bool,
+    #[arg(long, requires = "list_covered", help = "Gives --list-covered probabilities as exact fractions of the \
+        possibilities")]
+    exact: bool,
+    #[arg(long, value_enum, default_value_t = SearchOrder::Bfs, help = "The order open boards are searched in. \
+        Only the order possibilities are found in changes, not which ones are found")]
+    search: SearchOrder,
+    #[arg(long, value_enum, default_value_t = Adjacency::King, help = "Which surrounding cells a number counts, \
+        for variants that only count some of them")]
+    adjacency: Adjacency,
+    #[arg(long, value_name = "N", help = "Once more than N boards are waiting to be searched, switches \
+        --search bfs to depth first search. The result is just as exact but possibilities are found in a \
+        different order, and memory still grows with the number of boards visited")]
+    max_frontier: Option<usize>,
+    #[arg(long, value_enum, default_value_t = GuessStrategy::Safest, help = "How the best guess is chosen when \
+        no cell is guaranteed safe")]
+    guess_strategy: GuessStrategy,
+    #[arg(long, conflicts_with = "report_forced", help = "Only searches one of each set of boards that are \
+        reflections or rotations of each other, when the board after static analysis is symmetric. Saves \
+        search time on symmetric boards at the cost of comparing every board to its reflections")]
+    symmetry: bool,
+    #[arg(long, conflicts_with_all = ["animate", "self_check", "prove"], help = "Skips static analysis and \
+        searches the input as it is. The guaranteed cells are the same, only slower to find")]
+    no_static: bool,
+    #[arg(long, help = "Redraws the board after every pass of static analysis to show deductions as they're made")]
+    animate: bool,
+    #[arg(long, value_name = "MS", default_value_t = 300, help = "Delay between frames of --animate")]
+    animate_delay: u64,
+    #[arg(long, help = "Prints every possibility as a bomb mask - one bit per cell, row by row, packed into \
+        64 bit words written as 16 hex digits each")]
+    masks: bool,
+    #[arg(long, value_name = "HEX", help = "Places the bombs from a mask printed by \
+        --masks onto the input board and prints it")]
+    from_mask: Option<String>,
+    #[arg(long, value_name = "FILE", help = "Checks a complete mine layout against the board instead of solving it. \
+        FILE is a board of the same size where every 'X' is a mine and every other cell is safe. Exits with an \
+        error at the first number the layout doesn't match")]
+    solution: Option<PathBuf>,
+    #[arg(long, help = "Only prints the input board as rows of space separated cell ids: 0 - 8 numbers, 9 \
+        covered, 10 bomb, 11 unknown value, 12 absent, 13 flagged and 14 a range of values")]
+    matrix: bool,
+    #[arg(long, requires = "matrix", help = "Writes each --matrix cell as 15 values, a 1 at its id and 0 \
+        everywhere else")]
+    one_hot: bool,
+    #[arg(long, help = "Prints how long parsing, static analysis, the search and compiling the result took to stderr")]
+    timings: bool,
+    #[arg(long, value_enum, value_name = "FORMAT", help = "Prints the phase timings, the most open boards, boards \
+        visited, possibilities found and regions searched to stderr at the end of every run, for scripts to collect, \
+        with zero counts when it ends before the search")]
+    metrics: Option<MetricsFormat>,
+    #[arg(long, help = "Replaces unrecognised characters with revealed cells of unknown value ('~') instead of \
+        failing, warning about each one")]
+    lenient: bool,
+    #[arg(long, help = "Prints the bomb positions of every possibility instead of the guaranteed cells")]
+    configurations: bool,
+    #[arg(long, conflicts_with = "ints", help = "Pads rows shorter than the longest with absent cells, which \
+        aren't part of the board, instead of failing. Spaces are also read as absent cells")]
+    ragged: bool,
+    /// Removed now that numbers larger than their count of neighbours are always rejected. Kept
+    /// only to say so, rather than letting scripts passing it fail with an unknown argument
+    #[arg(long, hide = true)]
+    validate_strict: bool,
+    #[arg(long, help = "Rejects boards with known bombs that have no numbered neighbour, which nothing on the \
+        board confirms and are likely mistakes. Without it they're only warned about")]
+    strict_bombs: bool,
+    #[arg(long, help = "When the board is invalid, also lists the fewest numbers that would need a different \
+        value for the board to have a solution, as the likely misread cells. Can be slow, as it searches the \
+        board once per combination tried")]
+    suspects: bool,
+    /// Prints a small solvable board of covered cells, bombs, flags, empty cells and numbers, to pipe straight
+    /// back in
+    #[arg(long, hide = true)]
+    example: bool,
+    #[arg(long, help = "Lowers numbers larger than their count of neighbours under --adjacency, such as an 8 in \
+        a corner, to that count instead of rejecting the board, warning about each one")]
+    clamp_values: bool,
+    #[arg(long, value_name = "N", default_value_t = 2, global = true, help = "Decimal places to print percentages \
+        and other probabilities with, replay's included")]
+    precision: usize,
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percentage, help = "Marks cells that are a bomb in \
+        at least PERCENT% of possibilities, but not all of them, with '!'")]
+    likely_bombs: Option<f64>,
+    #[arg(long, value_name = "X,Y", value_parser = parse_pair, help = "Prints only the part of the result that \
+        fits in the terminal around [X, Y], with coordinate rulers")]
+    view: Option<(usize, usize)>,
+    #[arg(long, value_name = "COLUMNS,LINES", value_parser = parse_pair, requires = "view", help = "Size of the \
+        --view window including its rulers [default: $COLUMNS,$LINES or 80,24]")]
+    view_size: Option<(usize, usize)>,
+    #[arg(long, help = "Also prints the ambiguous cell most likely to be empty when there are guaranteed cells, \
+        to plan the guess after revealing them")]
+    always_guess: bool,
+    #[arg(long, help = "Explains the chance of the recommended cell being safe from the possibilities it's a \
+        bomb in, and lists the next best cells")]
+    explain_guess: bool,
+    #[arg(long, help = "Labels groups of ambiguous cells where either every cell with the upper case letter or \
+        every cell with the lower case one is a bomb, never both")]
+    show_pairs: bool,
+    #[arg(long, conflicts_with = "view", help = "Highlights numbers whose bombs are all found, showing where \
+        the safe cells they make are")]
+    show_satisfied: bool,
+    #[arg(long, conflicts_with = "view", help = "Shows the number a guaranteed safe cell will reveal in place of \
+        its 'O' when every possibility agrees on it, in colour")]
+    predict: bool,
+    #[arg(long, help = "Assumes every mine touches a number, as in some variants, so covered cells without a \
+        numbered neighbour are safe, with or without --no-static. Gives wrong answers on normal boards")]
+    mines_touch_numbers: bool,
+    #[arg(long, help = "Also tries each covered cell next to a number as a bomb and as safe during static \
+        analysis, deciding it when one of those contradicts the numbers. Slower static analysis that can leave \
+        much less to search")]
+    trial_deduction: bool,
+    #[arg(long, conflicts_with_all = ["auto_legend", "lenient"], help = "Reads the input as rows of whitespace \
+        separated integers instead of characters: -1 covered, -2 known bomb, -3 unknown value and 0 - 8 numbers")]
+    ints: bool,
+    #[arg(long, conflicts_with_all = ["move_only", "status_line", "list_covered"], help = "Prints only the input \
+        board with every guaranteed bomb placed, to solve again after revealing the safe cells")]
+    apply: bool,
+    #[arg(long, help = "Prints only the recommended cell to reveal as 'x y', exiting with an error if there's \
+        no move to make")]
+    move_only: bool,
+    #[arg(long, conflicts_with = "move_only", help = "Prints only a one line summary like \
+        'safe:5 bombs:3 ambiguous:12 best@(3,7):82%', where best is the recommended cell and its chance of being \
+        safe, or 'best:none'")]
+    status_line: bool,
+    #[arg(long, requires = "move_only", help = "Never recommends a cell that isn't guaranteed safe")]
+    no_guess: bool,
+    #[arg(long, value_name = "P", value_parser = parse_probability, requires = "move_only", help = "Only recommends \
+        a guess that's safe with at least probability P, between 0 and 1")]
+    min_safe_prob: Option<f64>,
+    #[arg(long, value_name = "X,Y,bomb|safe", value_parser = parse_what_if, help = "Prints the board static \
+        analysis leaves after assuming the given cell is a bomb or safe, or why it can't be")]
+    what_if: Option<((usize, usize), bool)>,
+    #[arg(long, help = "Checks whether static analysis alone solves the board, printing each deduction it makes \
+        or the cells left for a guess. Exits with an error if a guess is needed")]
+    prove: bool,
+    #[arg(long, requires = "prove", help = "Also prints which numbers forced each --prove deduction, and the \
+        mines around them that did")]
+    explain: bool,
+    #[arg(long, help = "Classifies the board as trivial (static analysis solves it), logic (the search solves \
+        it without a guess) or guess, with how many covered cells are forced")]
+    difficulty: bool,
+    /// Checks every static deduction against an exhaustive search of the input, exiting with an
+    /// error if any possibility contradicts one
+    #[arg(long, hide = true)]
+    self_check: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Shows which cells changed between two boards of the same size
+    Compare {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    /// Combines two partial observations of the same board, keeping whatever either knows per cell
+    Merge {
+        first: PathBuf,
+        second: PathBuf,
+    },
+    /// Judges each move of a game from successive boards, separated by blank lines or '---'
+    Replay {
+        file: PathBuf,
+    },
+    /// Solves every board in a file, separated as for replay, printing each result as a line of
+    /// JSON with the board's index as soon as it's solved
+    Batch {
+        file: PathBuf,
+    },
+}
+
+/// Environment variable holding the board when no file is given
+const BOARD_VARIABLE: &str = "MINESWEEPER_BOARD";
+
+/// Board printed by `--example`, solvable without a guess
+const EXAMPLE_BOARD: &str = "?X?-1??-\n111-1F1-\n-1111221\n-1??-1??\n";
+
+/// Most numbers `suspect_numbers` tries changing beyond those that are wrong by themselves
+const SUSPECT_LIMIT: usize = 2;
+
+/// Cells to decide from which `estimated_search_size` is worth a warning before searching
+const LARGE_SEARCH: usize = 40;
+
+const DIM: &str = "\x1b[2m";
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const PREDICTED: &str = "\x1b[1;36m";
+const RESET: &str = "\x1b[0m";
+
+/// Characters `--heatmap` falls back to without truecolor, from safest to most likely a bomb
+const HEAT_RAMP: &[char] = &['.', ':', ';', '+', '=', '*', '%', '#'];
+
+fn parse_mine_count(input: &str) -> Result<MineCount, String> {
+    let (rect, mines) = input.split_once('=').ok_or("Expected 'x,y,w,h=N'")?;
+    let (x, y, w, h) = parse_rect(rect)?;
+    let (min, max) = parse_mine_range(mines)?;
+
+    Ok(MineCount { x, y, w, h, min, max })
+}
+
+/// Parses `N`, `A..B` or `A..=B` into the inclusive bounds on the number of mines
+fn parse_mine_range(input: &str) -> Result<(usize, usize), String> {
+    let number = |v: &str| v.trim().parse::<usize>().map_err(|_| format!("'{v}' is not a valid number"));
+
+    let (min, max) = if let Some((min, max)) = input.split_once("..=") {
+        (number(min)?, number(max)?)
+    } else if let Some((min, max)) = input.split_once("..") {
+        let max = number(max)?.checked_sub(1).ok_or(format!("'{input}' doesn't contain any number of mines"))?;
+        (number(min)?, max)
+    } else {
+        let mines = number(input)?;
+        (mines, mines)
+    };
+
+    if min > max {
+        return Err(format!("'{input}' doesn't contain any number of mines"));
+    }
+    Ok((min, max))
+}
+
+fn parse_mask(input: &str) -> Result<Vec<u64>, String> {
+    let digits = input.trim().chars().collect_vec();
+    if digits.is_empty() || !digits.len().is_multiple_of(16) {
+        return Err("Expected 16 hex digits per 64 bit word".to_string());
+    }
+
+    digits.chunks(16)
+        .map(|word| {
+            let word = word.iter().collect::<String>();
+            u64::from_str_radix(&word, 16).map_err(|_| format!("'{word}' is not a valid hex word"))
+        })
+        .collect()
+}
+
+fn mask_to_hex(mask: &[u64]) -> String {
+    mask.iter().map(|word| format!("{word:016x}")).collect()
+}
+
+fn parse_rect(input: &str) -> Result<(usize, usize, usize, usize), String> {
+    let values = input.split(',')
+        .map(|v| v.trim().parse::<usize>().map_err(|_| format!("'{v}' is not a valid number")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [x, y, w, h] => Ok((x, y, w, h)),
+        _ => Err("Expected four comma separated values 'x,y,w,h'".to_string())
+    }
+}
+
+fn parse_pair(input: &str) -> Result<(usize, usize), String> {
+    let values = input.split(',')
+        .map(|v| v.trim().parse::<usize>().map_err(|_| format!("'{v}' is not a valid number")))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [x, y] => Ok((x, y)),
+        _ => Err("Expected two comma separated values".to_string())
+    }
+}
+
+/// Parses 'X,Y,bomb' or 'X,Y,safe', returning whether the cell is assumed to be a bomb
+fn parse_what_if(input: &str) -> Result<((usize, usize), bool), String> {
+    let (cell, assumption) = input.rsplit_once(',').ok_or("Expected X,Y,bomb or X,Y,safe")?;
+    let bomb = match assumption.trim() {
+        "bomb" => true,
+        "safe" => false,
+        other => return Err(format!("'{other}' is neither 'bomb' nor 'safe'")),
+    };
+    Ok((parse_pair(cell)?, bomb))
+}
+
+fn parse_percentage(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(percentage) if (0.0..=100.0).contains(&percentage) => Ok(percentage),
+        _ => Err(format!("'{input}' is not a percentage between 0 and 100")),
+    }
+}
+
+fn parse_probability(input: &str) -> Result<f64, String> {
+    match input.trim().parse::<f64>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err(format!("'{input}' is not a probability between 0 and 1")),
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    // Whether to print the human readable progress around the result
+    let verbose = !args.json && !args.list_covered && !args.move_only && !args.status_line && !args.apply && !args.rank_moves && !args.forced_only && !args.actions
+        && !args.difficulty && !args.prove;
+    // Whether to search even when static analysis leaves nothing to search, to report on the safe cells
+    let always_search = args.move_only || args.status_line || args.apply || args.actions;
 
-    let data = if let Ok(data) = fs::read_to_string(args.file) {
-        data
-    } else {
-        println!("Failed to read input file");
+    if args.validate_strict {
+        report_error(args.json, "--validate-strict has been removed - numbers larger than their count of neighbours \
+            are always rejected now, so leave it out, or pass --clamp-values to lower them instead");
+        std::process::exit(1);
+    }
+
+    if args.example {
+        print!("{EXAMPLE_BOARD}");
+        return;
+    }
+
+    if let Some(Command::Compare { old, new }) = &args.command {
+        compare(old, new);
+        return;
+    }
+
+    if let Some(Command::Merge { first, second }) = &args.command {
+        merge(first, second);
+        return;
+    }
+
+    if let Some(Command::Replay { file }) = &args.command {
+        replay(file, args.precision);
+        return;
+    }
+
+    if let Some(Command::Batch { file }) = &args.command {
+        batch(file);
         return;
+    }
+
+    let mut metrics = Metrics::new(args.metrics, Timings::new(args.timings));
+    let phase_start = Instant::now();
+
+    let parsed = if let Some(cells) = &args.flat {
+        Board::from_flat(cells, args.width.unwrap()).map(|board| (board, Vec::new()))
+    }
+    else {
+        // --file takes precedence over a piped board, which takes precedence over the environment
+        // variable. An empty stdin, as many CI runners give, counts as no board piped in, and without
+        // the variable parses as any other empty input.
+        let data = match &args.file {
+            Some(path) => fs::read_to_string(path).map_err(|_| "Failed to read input file"),
+            None if !io::stdin().is_terminal() => match io::read_to_string(io::stdin()) {
+                Ok(data) if data.trim().is_empty() => Ok(std::env::var(BOARD_VARIABLE).unwrap_or(data)),
+                Ok(data) => Ok(data),
+                Err(_) => Err("Failed to read the board from stdin"),
+            },
+            None => std::env::var(BOARD_VARIABLE)
+                .map_err(|_| "No board given - use --file or --flat, set MINESWEEPER_BOARD or pipe the board in"),
+        };
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                report_failure(&args, e);
+                return;
+            }
+        };
+
+        let legend = if args.auto_legend {
+            let detected = Legend::detect(&data);
+            if verbose {
+                match &detected {
+                    Some(legend) => println!("Detected legend: {legend}\n"),
+                    None => println!("Couldn't detect the legend, using the default symbols\n"),
+                }
+            }
+            detected.unwrap_or_default()
+        } else {
+            Legend::default()
+        };
+
+        let options = ParseOptions {
+            legend,
+            lenient: args.lenient,
+            ints: args.ints,
+            ragged: args.ragged,
+            adjacency: args.adjacency,
+            clamp: args.clamp_values,
+        };
+
+        if args.sparse {
+            Board::from_sparse(&data).map(|board| (board, Vec::new()))
+        }
+        else {
+            Board::from_string_with(data, &options)
+        }
     };
 
-    let initial_board = match Board::from_string(data) {
-        Ok(board) => board,
+    let mut initial_board = match parsed {
+        Ok((board, substitutions)) => {
+            for (x, y, c) in &substitutions {
+                eprintln!("Warning: replaced unrecognised character '{}' at position [{x}, {y}] with an unknown value", c.escape_debug());
+            }
+            if !substitutions.is_empty() {
+                eprintln!("Replaced {} unrecognised character(s)", substitutions.len());
+            }
+            board
+        }
         Err(e) => {
-            println!("{e}");
+            report_failure(&args, &e.to_string());
             return;
         }
     };
+    initial_board.set_adjacency(args.adjacency);
+
+    if args.clamp_values {
+        for (x, y, before) in initial_board.clamp_values() {
+            eprintln!("Warning: lowered '{}' at position [{x}, {y}] to '{}'", before.text(), initial_board.board[y][x].text());
+        }
+    }
+
+    let cropped = match (args.crop, args.crop_with_border) {
+        (Some((x, y, w, h)), _) => Some(initial_board.crop(x, y, w, h)),
+        (_, Some((x, y, w, h))) => Some(initial_board.crop_with_border(x, y, w, h)),
+        _ => None,
+    };
+
+    if let Some(cropped) = cropped {
+        initial_board = match cropped {
+            Ok(board) => board,
+            Err(e) => {
+                report_failure(&args, &e.to_string());
+                return;
+            }
+        };
+    }
+
+    if args.open_border {
+        initial_board.open_border();
+    }
+
+    let whole_board = args.mines.map(|(min, max)| MineCount { x: 0, y: 0, w: initial_board.width, h: initial_board.height, min, max });
+    for mine_count in whole_board.iter().chain(&args.region_mines) {
+        if mine_count.x + mine_count.w > initial_board.width || mine_count.y + mine_count.h > initial_board.height {
+            report_failure(&args, &format!("{mine_count} doesn't fit in the {}x{} board", initial_board.width, initial_board.height));
+            return;
+        }
+        initial_board.mine_counts.push(mine_count.clone());
+    }
 
-    println!("Input:\n{initial_board}\n");
+    if !args.mine_cells.is_empty() {
+        initial_board = match initial_board.with_mines(&args.mine_cells) {
+            Ok(board) => board,
+            Err(e) => {
+                report_failure(&args, &e.to_string());
+                return;
+            }
+        };
+    }
+
+    if args.matrix {
+        print!("{}", initial_board.matrix(args.one_hot));
+        return;
+    }
+
+    if verbose {
+        println!("Input:\n{initial_board}\n");
+    }
+
+    if let Some(path) = &args.solution {
+        let Some(solution) = read_board(path) else {
+            drop(metrics);
+            std::process::exit(1);
+        };
+        match initial_board.verify_solution(&solution) {
+            Ok(()) if args.json => println!("{}", JsonObject::new().field("valid", &true).to_json()),
+            Ok(()) => println!("Solution is valid"),
+            Err(e) => {
+                if args.json {
+                    println!("{}", JsonObject::new().field("valid", &false).field("error", &e.to_string()).to_json());
+                }
+                else {
+                    println!("Invalid solution:\n\t{e}");
+                }
+                drop(metrics);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
-    match initial_board.validate_board() {
-        Ok(remaining) => {
-            if remaining == 0 {
-                println!("Board already solved");
+    if let Some(mask) = &args.from_mask {
+        let mask = match parse_mask(mask) {
+            Ok(mask) => mask,
+            Err(e) => {
+                report_error(args.json, &e);
                 return;
             }
+        };
+
+        match initial_board.with_bomb_mask(&mask) {
+            Ok(board) if args.json => println!("{}", board.json_summary().to_json()),
+            Ok(board) => println!("Board from mask:\n{board}"),
+            Err(e) => report_error(args.json, &e.to_string()),
+        }
+        return;
+    }
+
+    if let Err(e) = initial_board.validate_board() {
+        if args.move_only {
+            drop(metrics);
+            std::process::exit(1);
+        }
+        let suspects = if args.suspects { initial_board.suspect_numbers() } else { None };
+        if args.json && args.suspects {
+            println!("{}", JsonObject::new()
+                .field("error", &format!("Invalid board:\n\t{e}"))
+                .field("suspects", &suspects.map(|cells| cells.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec()))
+                .to_json());
+            return;
+        }
+
+        report_error(args.json, &format!("Invalid board:\n\t{e}"));
+        match suspects {
+            Some(cells) => println!("Suspect numbers: {}", cells.iter().map(|(x, y)| format!("[{x}, {y}]")).join(" ")),
+            None if args.suspects => println!("No suspect numbers found - changing up to {SUSPECT_LIMIT} numbers besides those wrong by themselves leaves no solution"),
+            None => {}
+        }
+        return;
+    }
+
+    let unsupported = initial_board.unsupported_bombs();
+    if let (true, Some(&(x, y))) = (args.strict_bombs, unsupported.first()) {
+        drop(metrics);
+        report_failure(&args, &format!("Invalid board:\n\t{}", SolverError::UnsupportedBomb { x, y }));
+        std::process::exit(1);
+    }
+    for (x, y) in unsupported {
+        eprintln!("Warning: {}", SolverError::UnsupportedBomb { x, y });
+    }
+
+    let mut rules = rules::default_rules();
+    if args.mines_touch_numbers {
+        rules.push(Box::new(rules::MinesTouchNumbers));
+    }
+    if args.trial_deduction {
+        rules.push(Box::new(rules::TrialDeduction));
+    }
+
+    if let Some(((x, y), bomb)) = args.what_if {
+        let (assumption, result) = if bomb {
+            ("a bomb", initial_board.assume_bomb(x, y, &rules))
+        } else {
+            ("safe", initial_board.assume_safe(x, y, &rules))
+        };
+        match result {
+            Ok(board) if args.json => println!("{}", board.json_summary().to_json()),
+            Ok(board) => println!("If [{x}, {y}] is {assumption}:\n{board}"),
+            Err(e) => {
+                report_error(args.json, &format!("[{x}, {y}] can't be {assumption}:\n\t{e}"));
+                drop(metrics);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.prove {
+        if !prove(&initial_board, &rules, args.explain, args.json) {
+            drop(metrics);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if args.rank_moves {
+        // Any --mines total is already one of the board's mine counts
+        let moves = initial_board.move_probabilities(None);
+        if args.json {
+            println!("{}", moves.iter().map(|&(x, y, probability)| JsonObject::new()
+                .field("x", &x)
+                .field("y", &y)
+                .field("bomb_probability", &probability)).collect_vec().to_json());
+        }
+        else {
+            for (x, y, probability) in moves {
+                println!("{x} {y} {:.*}%", args.precision, probability * 100.0);
+            }
+        }
+        return;
+    }
+
+    if args.forced_only {
+        let (safe, bombs) = initial_board.all_forced(args.trial_deduction);
+        if args.json {
+            let cells = |cells: &[(usize, usize)]| cells.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec();
+            println!("{}", JsonObject::new().field("safe", &cells(&safe)).field("bombs", &cells(&bombs)).to_json());
+        }
+        else {
+            for (x, y) in safe {
+                println!("safe {x} {y}");
+            }
+            for (x, y) in bombs {
+                println!("bomb {x} {y}");
+            }
+        }
+        return;
+    }
+
+    if args.difficulty {
+        let difficulty = initial_board.difficulty(&rules);
+        if args.json {
+            println!("{}", JsonObject::new()
+                .field("difficulty", difficulty.level.name())
+                .field("forced", &difficulty.forced)
+                .to_json());
+        }
+        else {
+            println!("Difficulty: {} - {} forced cell(s)", difficulty.level.name(), difficulty.forced);
+        }
+        return;
+    }
+
+    // Every layout is equally likely, so any guess would be meaningless
+    if initial_board.has_no_information() {
+        report_failure(&args, "No information available - reveal a cell or provide --mines");
+        return;
+    }
+
+    if initial_board.is_solved() && !initial_board.has_slack() && !always_search {
+        if args.json {
+            println!("{}", JsonObject::new()
+                .field("input", &initial_board.json_summary())
+                .field("solved", &true)
+                .to_json());
         }
-        Err(e) => { println!("Invalid board:\n\t{e}"); return; }
+        else {
+            println!("Board already solved");
+        }
+        return;
     }
 
+    metrics.timings.record("Parsing", phase_start);
+    let phase_start = Instant::now();
+
     let mut board = initial_board.clone();
 
-    while board.complete_solvable() {}
-    println!("After static analysis:\n{board}\n");
+    if args.no_static {
+        // Leave every deduction to the search. The variant's untouched cells are part of the
+        // puzzle's rules rather than a deduction, and the search skips them, so they're still revealed.
+        if args.mines_touch_numbers {
+            rules::MinesTouchNumbers.apply(&mut board);
+        }
+    }
+    else if args.animate && verbose {
+        let delay = Duration::from_millis(args.animate_delay);
+        let show = |board: &Board| {
+            // Clear the screen and move the cursor back to the top left
+            print!("\x1b[2J\x1b[H");
+            println!("Static analysis:\n{board}\n");
+            thread::sleep(delay);
+        };
+
+        show(&board);
+        board.apply_rules_with(&rules, show);
+    }
+    else {
+        board.apply_rules(&rules);
+    }
+
+    metrics.timings.record("Static analysis", phase_start);
+
+    if args.self_check && !self_check(&initial_board, &board) {
+        drop(metrics);
+        std::process::exit(1);
+    }
+
+    if board.covered_cells().next().is_none() && !always_search {
+        if args.json {
+            println!("{}", JsonObject::new()
+                .field("input", &initial_board.json_summary())
+                .field("solved", &true)
+                .field("solution", &board.to_string())
+                .to_json());
+        }
+        else {
+            println!("Solved deterministically:\n{board}");
+        }
+        return;
+    }
+
+    if verbose && !args.no_static {
+        println!("After static analysis:\n{board}\n");
+    }
+
+    if let Some(path) = &args.export_dot {
+        if fs::write(path, board.constraint_dot()).is_err() {
+            report_failure(&args, &format!("Failed to write '{}'", path.display()));
+            return;
+        }
+    }
+
+    if args.configurations {
+        // Any --mines total is already one of the board's mine counts
+        for bombs in board.mine_configurations(None) {
+            if args.json {
+                println!("{}", bombs.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec().to_json());
+            }
+            else {
+                println!("{}", bombs.iter().map(|(x, y)| format!("[{x}, {y}]")).join(" "));
+            }
+        }
+        return;
+    }
+
+    let size = board.estimated_search_size();
+    if size.cells >= LARGE_SEARCH {
+        eprintln!(
+            "Warning: large search ({} cells to decide, largest region of {} cells, up to ~2^{} boards) - consider --crop to solve part of the board",
+            size.cells, size.largest_region, size.cells
+        );
+    }
 
-    let mut open_boards = VecDeque::new();
+    let mut frontier = Frontier::new(args.search, args.max_frontier);
     let mut visited = HashSet::new();
     let mut possibilities = Vec::new();
-    let mut ignore = HashSet::new();
+    let ignore = board.unconstrained_cells();
+    let mut live = (args.report_forced && verbose).then(|| LiveBranches::new(&board));
+    let start = board.clone();
+    let symmetries = if args.symmetry { start.symmetries() } else { Vec::new() };
 
-    let mut first = true;
-    while !open_boards.is_empty() || first {
-        let board = if !first {
-            open_boards.pop_front().unwrap()
+    let phase_start = Instant::now();
+    // Static analysis can place every bomb while leaving cells no number touches covered
+    if board.is_solved() {
+        if args.show_possibilities && verbose {
+            println!("Possible board found:\n{board}\n");
         }
-        else {
-            first = false;
-            board.clone()
-        };
+        possibilities.push(board.clone());
+    }
+    if !board.is_solved() || board.has_slack() {
+        frontier.push(board);
+    }
+    while let Some(board) = frontier.pop() {
+        let (solved_boards, new_open_boards) = board.get_possible_boards(&mut visited, &ignore, &symmetries);
 
-        let (solved_boards, new_open_boards) = board.get_possible_boards(&mut visited, &mut ignore);
+        if let Some(live) = &mut live {
+            live.remove(&board);
+            solved_boards.iter().chain(&new_open_boards).for_each(|board| live.add(board));
+            for (x, y) in live.newly_forced(&start) {
+                println!("Cell at position [{x}, {y}] is a bomb in all live branches");
+            }
+        }
 
-        for board in solved_boards {
-            if args.show_possibilities {
+        for board in solved_boards.iter().flat_map(|board| board.orbit(&symmetries)) {
+            if args.show_possibilities && verbose {
                 println!("Possible board found:\n{board}\n");
             }
             possibilities.push(board);
         }
 
         for board in new_open_boards {
-            open_boards.push_back(board);
+            frontier.push(board);
         }
     }
 
-    println!("Finished finding solutions - {} possibilities\n", possibilities.len());
+    metrics.timings.record("Search", phase_start);
+    metrics.peak_open_boards = frontier.peak;
+    metrics.visited = visited.len();
+    metrics.possibilities = possibilities.len();
+    metrics.regions = start.regions().len();
 
-    let guaranteed = Board::compile_guaranteed(&initial_board, &possibilities, &ignore);
-    println!("{guaranteed}");
+    if possibilities.is_empty() {
+        drop(metrics);
+        report_failure(&args, "No layout of bombs is consistent with the board");
+        std::process::exit(1);
+    }
 
-    if !args.show_possibilities {
-        println!("Use the -s argument to show individual board possibilities");
+    let phase_start = Instant::now();
+    let mut guaranteed = Board::compile_guaranteed(&initial_board, &possibilities, &ignore);
+    guaranteed.use_strategy(&initial_board, args.guess_strategy);
+    // The strategy may have moved the best guess off the cell the 50/50 was found for
+    guaranteed.find_fifty_fifty(&possibilities);
+    if args.always_guess {
+        guaranteed.find_ambiguous_guess();
     }
-}
+    if let Some(percentage) = args.likely_bombs {
+        guaranteed.mark_likely_bombs(percentage);
+    }
+    if args.show_pairs {
+        guaranteed.mark_pairs(&possibilities);
+    }
+    if args.show_satisfied {
+        guaranteed.mark_satisfied(&initial_board);
+    }
+    if args.predict {
+        guaranteed.mark_predicted(&possibilities, &ignore);
+    }
+    if let Some(centre) = args.view {
+        guaranteed.view = Some(View { centre, size: args.view_size.unwrap_or_else(View::terminal_size) });
+    }
+    metrics.timings.record("Compiling guaranteed cells", phase_start);
 
-#[derive(Debug, Copy, Clone)]
-enum CellTypes {
-    Covered,
-    Bomb,
-    Value(u8),
-}
+    if args.density && verbose {
+        print_density(&start, &possibilities, args.mines, args.precision);
+    }
 
-impl CellTypes {
-    pub fn from_char(input: char) -> Result<CellTypes, String> {
-        match input {
-            '-' => Ok(CellTypes::Value(0)),
-            '?' => Ok(CellTypes::Covered),
-            'X' | 'x' => Ok(CellTypes::Bomb),
-            '1' => Ok(CellTypes::Value(1)),
-            '2' => Ok(CellTypes::Value(2)),
-            '3' => Ok(CellTypes::Value(3)),
-            '4' => Ok(CellTypes::Value(4)),
-            '5' => Ok(CellTypes::Value(5)),
-            '6' => Ok(CellTypes::Value(6)),
-            '7' => Ok(CellTypes::Value(7)),
-            '8' => Ok(CellTypes::Value(8)),
-            c => Err(format!("Unrecognised character '{}'", c.escape_debug())),
+    if args.region_report && verbose {
+        print_region_report(&start, &possibilities);
+    }
+
+    if args.dump_regions && verbose {
+        print_regions(&start, &guaranteed, &initial_board, args.precision);
+    }
+
+    if args.heatmap && verbose {
+        print_heatmap(&guaranteed, &initial_board, args.heatmap_percent);
+    }
+
+    if args.move_only {
+        let allowed = |safe: f64| safe >= 1.0 || !args.no_guess && safe >= args.min_safe_prob.unwrap_or(0.0);
+        match guaranteed.recommended_move(&initial_board) {
+            Some(((x, y), safe)) if allowed(safe) => println!("{x} {y}"),
+            _ => {
+                drop(metrics);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.status_line {
+        let cells = guaranteed.covered_cells(&initial_board);
+        let count = |status: CellStatus| cells.iter().filter(|cell| cell.2 == status).count();
+        let best = match guaranteed.recommended_move(&initial_board) {
+            Some(((x, y), safe)) => format!("best@({x},{y}):{:.0}%", safe * 100.0),
+            None => "best:none".to_string(),
+        };
+        println!("safe:{} bombs:{} ambiguous:{} {best}", count(CellStatus::Safe), count(CellStatus::Bomb), count(CellStatus::Ambiguous));
+        return;
+    }
+
+    if args.apply {
+        let board = guaranteed.applied(&initial_board);
+        if args.json {
+            println!("{}", board.json_summary().to_json());
+        }
+        else {
+            println!("{board}");
+        }
+        return;
+    }
+
+    if args.actions {
+        let (click, flag) = guaranteed.actions(&initial_board);
+        let guess = guaranteed.best_guess.map(|(cell, _)| cell);
+        if args.json {
+            let cells = |cells: &[(usize, usize)]| cells.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec();
+            println!("{}", JsonObject::new()
+                .field("flag", &cells(&flag))
+                .field("click", &cells(&click))
+                .field("guess", &guess.map(|(x, y)| JsonObject::new().field("x", &x).field("y", &y)))
+                .to_json());
+        }
+        else {
+            let cells = |cells: &[(usize, usize)]| cells.iter().map(|(x, y)| format!(" [{x}, {y}]")).collect::<String>();
+            println!("flag:{}", cells(&flag));
+            println!("click:{}", cells(&click));
+            if let Some((x, y)) = guess {
+                println!("guess: [{x}, {y}]");
+            }
+        }
+        return;
+    }
+
+    if args.list_covered {
+        let cells = guaranteed.covered_cells(&initial_board);
+        if args.json {
+            println!("{}", guaranteed.covered_json(&initial_board, args.exact).to_json());
+        }
+        else {
+            for (x, y, status, probability) in cells {
+                match (probability, guaranteed.bomb_fraction(x, y)) {
+                    (Some(_), Some((numerator, denominator))) if args.exact => println!("{x} {y} {} {numerator}/{denominator}", status.name()),
+                    (Some(probability), _) => println!("{x} {y} {} {:.*}%", status.name(), args.precision, probability * 100.0),
+                    (None, _) => println!("{x} {y} {} unknown", status.name()),
+                }
+            }
+        }
+        return;
+    }
+
+    let masks = args.masks.then(|| possibilities.iter().map(|board| mask_to_hex(&board.bomb_mask())).collect_vec());
+    let bounds = if args.mines.is_none() { start.bomb_count_bounds(&possibilities) } else { None };
+
+    if args.json {
+        let mut object = guaranteed.to_json(&initial_board, &possibilities, args.show_possibilities);
+        if let Some(masks) = &masks {
+            object = object.field("masks", masks);
+        }
+        if args.predict {
+            object = object.field("predicted", &guaranteed.predicted.iter()
+                .map(|&((x, y), value)| JsonObject::new().field("x", &x).field("y", &y).field("value", &(value as usize)))
+                .collect_vec());
+        }
+        if let Some((min, max)) = bounds {
+            object = object.field("bomb_count", &JsonObject::new().field("min", &min).field("max", &max));
+        }
+        if args.mine_histogram {
+            let histogram = Board::bomb_count_histogram(&possibilities).into_iter()
+                .fold(JsonObject::new(), |histogram, (total, count)| histogram.field(&total.to_string(), &count));
+            object = object.field("mine_histogram", &histogram);
+        }
+        println!("{}", object.to_json());
+        return;
+    }
+
+    println!("Finished finding solutions - {} possibilities\n", possibilities.len());
+
+    match bounds {
+        Some((min, max)) if min == max => println!("{min} mine(s) in total\n"),
+        Some((min, max)) => println!("Between {min} and {max} mines in total\n"),
+        None => {}
+    }
+
+    if args.mine_histogram {
+        println!("Possibilities by total mines:");
+        for (total, count) in Board::bomb_count_histogram(&possibilities) {
+            println!("\t{total} mine(s): {count}");
+        }
+        let unconstrained = start.unconstrained_cells().len();
+        if unconstrained > 0 {
+            println!("\tEach may have up to {unconstrained} more in cells no number touches");
+        }
+        println!();
+    }
+
+    if let Some(masks) = &masks {
+        println!("Bomb masks:");
+        for mask in masks {
+            println!("\t{mask}");
+        }
+        println!();
+    }
+    println!("{guaranteed:.*}", args.precision);
+
+    if args.explain_guess {
+        print_guess_explanation(&guaranteed, &initial_board, args.precision);
+    }
+
+    if !args.show_possibilities {
+        println!("Use the -s argument to show individual board possibilities");
+    }
+}
+
+/// Reads and parses a board for a subcommand, printing why if it can't
+fn read_board(path: &PathBuf) -> Option<Board> {
+    let Ok(data) = fs::read_to_string(path) else {
+        println!("Failed to read input file '{}'", path.display());
+        return None;
+    };
+
+    match data.parse::<Board>() {
+        Ok(board) => Some(board),
+        Err(e) => {
+            println!("{}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn merge(first: &PathBuf, second: &PathBuf) {
+    let (Some(first), Some(second)) = (read_board(first), read_board(second)) else { return; };
+
+    match first.merge(&second) {
+        Ok(board) => println!("{board}"),
+        Err(e) => println!("{e}"),
+    }
+}
+
+/// Splits a file holding several boards on blank lines and lines of '---'
+fn split_boards(data: &str) -> Vec<String> {
+    let mut boards = vec![String::new()];
+    for line in data.lines() {
+        if line.trim().is_empty() || line.trim() == "---" {
+            boards.push(String::new());
+        }
+        else {
+            boards.last_mut().unwrap().push_str(&format!("{line}\n"));
+        }
+    }
+    boards.retain(|board| !board.is_empty());
+    boards
+}
+
+/// Solves each board of a file in turn, printing its result as one line of JSON as soon as it's found
+fn batch(path: &PathBuf) {
+    let Ok(data) = fs::read_to_string(path) else {
+        println!("{}", JsonObject::new().field("error", "Failed to read input file").to_json());
+        return;
+    };
+
+    let rules = rules::default_rules();
+    for (index, input) in split_boards(&data).into_iter().enumerate() {
+        let object = match Board::from_string(input) {
+            Err(e) => JsonObject::new().field("error", &e.to_string()),
+            Ok(board) => match board.validate_board() {
+                Err(e) => JsonObject::new().field("error", &format!("Invalid board:\n\t{e}")),
+                Ok(_) if board.covered_cells().next().is_none() => JsonObject::new()
+                    .field("input", &board.json_summary())
+                    .field("solved", &true),
+                Ok(_) => {
+                    let (result, possibilities) = board.solve(&rules);
+                    result.to_json(&board, &possibilities, false)
+                }
+            },
+        };
+        println!("{}", object.field("index", &index).to_json());
+    }
+}
+
+fn replay(path: &PathBuf, precision: usize) {
+    let Ok(data) = fs::read_to_string(path) else {
+        println!("Failed to read input file '{}'", path.display());
+        return;
+    };
+
+    let snapshots = split_boards(&data);
+    let mut boards = Vec::with_capacity(snapshots.len());
+    for (i, snapshot) in snapshots.into_iter().enumerate() {
+        match Board::from_string(snapshot) {
+            Ok(board) => boards.push(board),
+            Err(e) => {
+                println!("Board {}: {e}", i + 1);
+                return;
+            }
+        }
+    }
+
+    let rules = rules::default_rules();
+    let (mut safe, mut guesses, mut mistakes) = (0, 0, 0);
+    for (i, pair) in boards.windows(2).enumerate() {
+        let (before, after) = (&pair[0], &pair[1]);
+        let changes = match before.diff(after) {
+            Ok(changes) => changes,
+            Err(e) => {
+                println!("Move {}: {e}", i + 1);
+                return;
+            }
+        };
+        let revealed = changes.iter()
+            .filter(|(_, _, old, new)| old.is_covered()
+                && matches!(new, CellTypes::Value(_) | CellTypes::UnknownValue | CellTypes::ValueRange { .. } | CellTypes::Bomb))
+            .map(|&(x, y, _, new)| (x, y, new))
+            .collect_vec();
+        if revealed.is_empty() {
+            println!("Move {}: No cells revealed", i + 1);
+            continue;
+        }
+        if before.validate_board().is_err() {
+            println!("Move {}: Board before the move is invalid", i + 1);
+            continue;
+        }
+
+        let (result, _) = before.solve(&rules);
+        let recommended = result.recommended_move(before);
+        let followed = match recommended {
+            Some((cell, _)) if revealed.iter().any(|&(x, y, _)| (x, y) == cell) => "followed the recommendation".to_string(),
+            Some(((x, y), chance)) => format!("the recommendation was [{x}, {y}] ({:.*}% safe)", precision, chance * 100.0),
+            None => "no recommendation".to_string(),
+        };
+
+        let verdict = match judge_move(before, &result, &revealed) {
+            Verdict::Mistake { forced: true } => {
+                mistakes += 1;
+                "mistake - revealed a cell that had to be a bomb".to_string()
+            }
+            Verdict::Mistake { forced: false } => {
+                mistakes += 1;
+                "mistake - revealed a mine".to_string()
+            }
+            Verdict::Safe => {
+                safe += 1;
+                "safe".to_string()
+            }
+            Verdict::Guess(chance) => {
+                guesses += 1;
+                match chance {
+                    Some(chance) => format!("guess - {:.*}% safe", precision, chance * 100.0),
+                    None => "guess - no information".to_string(),
+                }
+            }
+        };
+        println!("Move {}: {verdict}, {followed}", i + 1);
+    }
+
+    println!("\n{} move(s) - {safe} safe, {guesses} guess(es), {mistakes} mistake(s)", boards.len().saturating_sub(1));
+}
+
+/// How a move of a replay turned out, from what the solver knew before it
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Verdict {
+    Safe,
+    /// Revealed a cell that wasn't guaranteed safe, with its chance of being safe if any possibility decides it
+    Guess(Option<f64>),
+    /// Revealed a mine, or a cell that had to be a bomb when `forced`
+    Mistake { forced: bool },
+}
+
+/// Judges a move of a replay that revealed `revealed` on `before`, which `result` was solved from.
+/// Opening a zero opens its neighbours too, and clicking any zero of the area opens the same cells,
+/// so a move that opened zeros is judged by the safest of them. Otherwise every cell it revealed was
+/// clicked, and it's judged by the riskiest.
+fn judge_move(before: &Board, result: &SolveResult, revealed: &[(usize, usize, CellTypes)]) -> Verdict {
+    let cells = result.covered_cells(before);
+    let status = |x: usize, y: usize| cells.iter().find(|cell| (cell.0, cell.1) == (x, y)).map(|&(_, _, status, probability)| (status, probability));
+
+    if revealed.iter().any(|&(x, y, _)| matches!(status(x, y), Some((CellStatus::Bomb, _)))) {
+        return Verdict::Mistake { forced: true };
+    }
+    if revealed.iter().any(|(_, _, cell)| matches!(cell, CellTypes::Bomb)) {
+        return Verdict::Mistake { forced: false };
+    }
+
+    let zeros = revealed.iter().filter(|(_, _, cell)| matches!(cell, CellTypes::Value(0))).collect_vec();
+    let cascade = !zeros.is_empty();
+    let judged = if cascade { zeros } else { revealed.iter().collect_vec() };
+    let statuses = judged.iter().filter_map(|&&(x, y, _)| status(x, y)).collect_vec();
+
+    let is_safe = |cell: &(CellStatus, Option<f64>)| cell.0 == CellStatus::Safe;
+    if if cascade { statuses.iter().any(is_safe) } else { statuses.iter().all(is_safe) } {
+        return Verdict::Safe;
+    }
+
+    let chances = statuses.iter().filter(|cell| !is_safe(cell)).filter_map(|cell| cell.1).map(|bomb| 1.0 - bomb);
+    Verdict::Guess(if cascade { chances.reduce(f64::max) } else { chances.reduce(f64::min) })
+}
+
+fn compare(old: &PathBuf, new: &PathBuf) {
+    let (Some(old), Some(new)) = (read_board(old), read_board(new)) else { return; };
+    let (old, new) = (&old, &new);
+
+    let changes = match old.diff(new) {
+        Ok(changes) => changes,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+
+    let mut output = String::new();
+    for y in 0..new.height {
+        for x in 0..new.width {
+            let c = new.board[y][x].char();
+            if changes.iter().any(|&(cx, cy, _, _)| (cx, cy) == (x, y)) {
+                output.push_str(&format!("{HIGHLIGHT}{c}{RESET}"));
+            }
+            else {
+                output.push_str(&format!("{DIM}{c}{RESET}"));
+            }
+        }
+        output.push('\n');
+    }
+    println!("{output}");
+
+    if changes.is_empty() {
+        println!("No cells changed");
+        return;
+    }
+
+    println!("{} cell(s) changed:", changes.len());
+    for (x, y, before, after) in changes {
+        println!("\t[{}, {}]: {} -> {}", x, y, before.char(), after.char());
+    }
+}
+
+fn print_density(board: &Board, possibilities: &[Board], mines: Option<(usize, usize)>, precision: usize) {
+    let covered = board.covered_cells().count();
+    let placed = board.board.iter().flatten().filter(|cell| matches!(cell, CellTypes::Bomb)).count();
+
+    println!("Density:");
+    match mines {
+        Some((min, max)) if covered > 0 && min == max => {
+            let remaining = min.saturating_sub(placed);
+            println!("\tGlobal: {remaining} mine(s) left in {covered} covered cell(s) ({:.*}%)", precision, remaining as f64 / covered as f64 * 100.0);
+        }
+        Some((min, max)) if covered > 0 => {
+            let (min, max) = (min.saturating_sub(placed), max.saturating_sub(placed));
+            println!(
+                "\tGlobal: {min} - {max} mine(s) left in {covered} covered cell(s) ({:.*}% - {:.*}%)",
+                precision, min as f64 / covered as f64 * 100.0, precision, max as f64 / covered as f64 * 100.0
+            );
+        }
+        Some(_) => println!("\tGlobal: No covered cells left"),
+        None => println!("\tGlobal: Unknown - use --mines to give the total number of mines"),
+    }
+
+    for (i, region) in board.regions().iter().enumerate() {
+        if possibilities.is_empty() {
+            println!("\tRegion {} ({} cell(s)): No possibilities found", i + 1, region.len());
+            continue;
+        }
+
+        let bombs: usize = possibilities.iter()
+            .map(|possibility| region.iter().filter(|&&(x, y)| matches!(possibility.board[y][x], CellTypes::Bomb)).count())
+            .sum();
+        let expected = bombs as f64 / possibilities.len() as f64;
+
+        println!(
+            "\tRegion {} ({} cell(s)): {:.*} expected bomb(s) ({:.*}%)",
+            i + 1, region.len(), precision, expected, precision, expected / region.len() as f64 * 100.0
+        );
+    }
+    println!();
+}
+
+/// Prints how many possibilities the recommended cell is a bomb in, and the next best cells
+fn print_guess_explanation(result: &SolveResult, base: &Board, precision: usize) {
+    let Some(((x, y), _)) = result.recommended_move(base) else {
+        let cells = result.covered_cells(base);
+        let reason = if cells.is_empty() {
+            "no cell is left covered"
+        } else if cells.iter().all(|cell| cell.2 == CellStatus::Bomb) {
+            "every covered cell is a guaranteed bomb"
+        } else if cells.iter().all(|cell| cell.3.is_none()) {
+            "no covered cell is touched by a number"
+        } else if cells.iter().all(|cell| cell.2 == CellStatus::Bomb || cell.3.is_none()) {
+            "every covered cell a number touches is a guaranteed bomb"
+        } else {
+            "every cell that could be safe is flagged"
+        };
+        println!("No cell to recommend - {reason}\n");
+        return;
+    };
+    let describe = |x: usize, y: usize| {
+        let (bomb, not) = result.counts[y][x];
+        let chance = bomb as f64 / (bomb + not) as f64 * 100.0;
+        format!("a bomb in {bomb} of {} possibilities - {:.*}% bomb / {:.*}% safe", bomb + not, precision, chance, precision, 100.0 - chance)
+    };
+
+    println!("Recommended cell [{x}, {y}] is {}, the best available", describe(x, y));
+    let runners_up = result.covered_cells(base).into_iter()
+        .filter(|&(cx, cy, status, probability)| (cx, cy) != (x, y) && status != CellStatus::Bomb && probability.is_some())
+        .sorted_by(|a, b| a.3.partial_cmp(&b.3).unwrap())
+        .take(3)
+        .collect_vec();
+    if !runners_up.is_empty() {
+        println!("Runners up:");
+        for (x, y, _, _) in runners_up {
+            println!("\t[{x}, {y}]: {}", describe(x, y));
+        }
+    }
+    println!();
+}
+
+/// Prints each region of `board` as its own part of the guaranteed cells, with a ring of
+/// surrounding cells, followed by the status and chance of being a bomb of each of its cells
+fn print_regions(board: &Board, result: &SolveResult, base: &Board, precision: usize) {
+    let cells = result.covered_cells(base).into_iter().map(|(x, y, status, probability)| ((x, y), (status, probability))).collect::<HashMap<_, _>>();
+
+    for (i, region) in board.regions().iter().enumerate() {
+        let (Some((x0, x1)), Some((y0, y1))) = (region.iter().map(|cell| cell.0).minmax().into_option(), region.iter().map(|cell| cell.1).minmax().into_option()) else { continue; };
+        let (x0, y0, x1, y1) = (x0.saturating_sub(1), y0.saturating_sub(1), (x1 + 1).min(board.width - 1), (y1 + 1).min(board.height - 1));
+
+        println!("Region {} ({} cell(s)) from [{x0}, {y0}] to [{x1}, {y1}]:", i + 1, region.len());
+        for line in &result.output[y0..=y1] {
+            println!("\t{}", line[x0..=x1].iter().collect::<String>());
+        }
+        for &(x, y) in region {
+            match cells.get(&(x, y)) {
+                Some((status, Some(probability))) => println!("\t[{x}, {y}] {} {:.*}%", status.name(), precision, probability * 100.0),
+                Some((status, None)) => println!("\t[{x}, {y}] {} unknown", status.name()),
+                None => println!("\t[{x}, {y}] not covered"),
+            }
+        }
+        println!();
+    }
+}
+
+/// Whether the terminal claims 24 bit colour support
+fn truecolor() -> bool {
+    matches!(std::env::var("COLORTERM").as_deref(), Ok("truecolor" | "24bit"))
+}
+
+/// Prints `base` with each covered cell drawn by its chance of being a bomb - on a green to red
+/// background with truecolor, or from `HEAT_RAMP` otherwise. Cells no number touches have no
+/// probability so keep their own character.
+fn print_heatmap(result: &SolveResult, base: &Board, percentages: bool) {
+    let probabilities = result.covered_cells(base).into_iter()
+        .filter_map(|(x, y, _, probability)| probability.map(|probability| ((x, y), probability)))
+        .collect::<HashMap<_, _>>();
+    let colour = truecolor();
+    // Wide enough for ' 100%' when overlaying percentages, so neighbouring cells stay apart
+    let width = if percentages { 5 } else { 1 };
+
+    println!("Heatmap:");
+    for y in 0..base.height {
+        let mut line = String::new();
+        for x in 0..base.width {
+            let Some(&probability) = probabilities.get(&(x, y)) else {
+                line.push_str(&format!("{:>width$}", base.board[y][x].text()));
+                continue;
+            };
+
+            let text = if percentages {
+                format!("{:>4.0}%", probability * 100.0)
+            }
+            else if colour {
+                " ".to_string()
+            }
+            else {
+                HEAT_RAMP[((probability * HEAT_RAMP.len() as f64) as usize).min(HEAT_RAMP.len() - 1)].to_string()
+            };
+
+            if colour {
+                let (red, green) = ((probability * 255.0).round() as u8, ((1.0 - probability) * 255.0).round() as u8);
+                line.push_str(&format!("\x1b[30;48;2;{red};{green};0m{text}{RESET}"));
+            }
+            else {
+                line.push_str(&text);
+            }
+        }
+        println!("\t{line}");
+    }
+    println!();
+}
+
+/// Without mine counts regions don't constrain each other, so the possibilities are every
+/// combination of one layout per region and their product is the number of possibilities
+fn print_region_report(board: &Board, possibilities: &[Board]) {
+    println!("Regions:");
+    for (i, region) in board.regions().iter().enumerate() {
+        let layouts = possibilities.iter()
+            .map(|possibility| region.iter().map(|&(x, y)| matches!(possibility.board[y][x], CellTypes::Bomb)).collect_vec())
+            .unique()
+            .collect_vec();
+
+        match layouts.iter().map(|layout| layout.iter().filter(|&&bomb| bomb).count()).minmax().into_option() {
+            Some((min, max)) => println!(
+                "\tRegion {} ({} cell(s)): {} layout(s) with {min} - {max} bomb(s)", i + 1, region.len(), layouts.len()
+            ),
+            None => println!("\tRegion {} ({} cell(s)): No possibilities found", i + 1, region.len()),
+        }
+    }
+    println!();
+}
+
+/// Prints the deductions static analysis makes on `board` and whether they decide every covered
+/// cell, returning whether they do
+fn prove(board: &Board, rules: &[Box<dyn DeductionRule>], explain: bool, json: bool) -> bool {
+    let mut board = board.clone();
+    let steps = board.deduction_steps(rules, explain);
+    let undecided = board.covered_cells().collect_vec();
+
+    if json {
+        let step_objects = steps.iter().map(|(rule, cells, _)| JsonObject::new()
+            .field("rule", *rule)
+            .field("cells", &cells.iter().map(|&(x, y, cell)| JsonObject::new()
+                .field("x", &x)
+                .field("y", &y)
+                .field("cell", &cell.char().to_string())).collect_vec())).collect_vec();
+        let mut object = JsonObject::new()
+            .field("solvable", &undecided.is_empty())
+            .field("steps", &step_objects)
+            .field("undecided", &undecided.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec());
+        if explain {
+            // One entry per deduced cell, in the order they were deduced
+            let transcript = steps.iter()
+                .flat_map(|(rule, cells, reasons)| cells.iter().zip(reasons).map(move |(&(x, y, cell), reason)| JsonObject::new()
+                    .field("rule", *rule)
+                    .field("target", &JsonObject::new().field("x", &x).field("y", &y))
+                    .field("conclusion", if matches!(cell, CellTypes::Bomb) { "bomb" } else { "safe" })
+                    .field("supporting", &reason.to_json())))
+                .collect_vec();
+            object = object.field("transcript", &transcript);
+        }
+        println!("{}", object.to_json());
+        return undecided.is_empty();
+    }
+
+    println!("Deductions:");
+    for (i, (rule, cells, reasons)) in steps.iter().enumerate() {
+        let cells = cells.iter().map(|&(x, y, cell)| match cell {
+            CellTypes::Bomb => format!("[{x}, {y}] bomb"),
+            _ => format!("[{x}, {y}] safe"),
+        }).collect_vec();
+        println!("\t{}. {rule}: {}", i + 1, cells.join(", "));
+        for (cell, reason) in cells.iter().zip(reasons) {
+            println!("\t\t{cell} - {}", reason.describe(rule));
+        }
+    }
+    if steps.is_empty() {
+        println!("\tNone");
+    }
+    println!();
+
+    if undecided.is_empty() {
+        println!("Solvable without guessing:\n{board}");
+    }
+    else {
+        let cells = undecided.iter().map(|(x, y)| format!("[{x}, {y}]")).join(", ");
+        println!("A guess is needed - no rule decides the covered cell(s) {cells}:\n{board}");
+    }
+    undecided.is_empty()
+}
+
+/// Enumerates the possibilities of `initial` without static analysis and checks each agrees with
+/// every cell `analysed` deduced, reporting to stderr
+#[allow(clippy::needless_range_loop)]
+fn self_check(initial: &Board, analysed: &Board) -> bool {
+    let mut contradictions = vec![vec![0usize; initial.width]; initial.height];
+    let mut possibilities = 0;
+    for bombs in initial.mine_configurations(None) {
+        possibilities += 1;
+        for y in 0..initial.height {
+            for x in 0..initial.width {
+                let is_bomb = bombs.contains(&(x, y));
+                match (initial.board[y][x], analysed.board[y][x]) {
+                    (CellTypes::Covered | CellTypes::Flagged, CellTypes::Bomb) if !is_bomb => contradictions[y][x] += 1,
+                    (CellTypes::Covered | CellTypes::Flagged, CellTypes::Value(_)) if is_bomb => contradictions[y][x] += 1,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let mut passed = true;
+    for y in 0..initial.height {
+        for x in 0..initial.width {
+            if contradictions[y][x] > 0 {
+                eprintln!(
+                    "Self check failed: static analysis made the cell at position [{x}, {y}] '{}' but {} of {possibilities} possibilities disagree",
+                    analysed.board[y][x].char(), contradictions[y][x]
+                );
+                passed = false;
+            }
+        }
+    }
+    if passed {
+        eprintln!("Self check passed: static analysis agrees with all {possibilities} possibilities");
+    }
+    passed
+}
+
+/// Prints an error either as plain text or as a JSON object with an `error` field
+/// Reports an error that keeps the board from being solved. --move-only prints nothing but the move,
+/// so it exits with an error instead
+fn report_failure(args: &Args, message: &str) {
+    if args.move_only {
+        std::process::exit(1);
+    }
+    report_error(args.json, message);
+}
+
+fn report_error(json: bool, message: &str) {
+    if json {
+        println!("{}", JsonObject::new().field("error", message).to_json());
+    }
+    else {
+        println!("{message}");
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SolverError {
+    UnrecognisedCharacter(char),
+    UnrecognisedInteger(String),
+    IrregularWidth { expected: usize, found: usize },
+    EmptyInput,
+    InvalidCrop { x: usize, y: usize, w: usize, h: usize, width: usize, height: usize },
+    DimensionMismatch { first: (usize, usize), second: (usize, usize) },
+    TooManyBombs { x: usize, y: usize, excess: usize },
+    NotEnoughCells { x: usize, y: usize, required: usize, available: usize },
+    NeighboursDetermined { x: usize, y: usize, required: usize },
+    RegionTooManyBombs { region: MineCount, excess: usize },
+    RegionNotEnoughCells { region: MineCount, required: usize, available: usize },
+    TooManyMinesPlaced { placed: usize, declared: usize },
+    TooManyFlags { flagged: usize, declared: usize },
+    MaskLength { expected: usize, found: usize },
+    MaskOnRevealedCell { x: usize, y: usize },
+    CellOutOfBounds { x: usize, y: usize, width: usize, height: usize },
+    MineOnRevealedCell { x: usize, y: usize },
+    ValueExceedsNeighbours { x: usize, y: usize, value: u8, neighbours: usize },
+    MergeConflict { x: usize, y: usize, first: CellTypes, second: CellTypes },
+    CellNotCovered { x: usize, y: usize },
+    InvalidHeader(String),
+    InvalidLegend(String),
+    InvalidRange(String),
+    HeaderSizeMismatch { declared: (usize, usize), found: (usize, usize) },
+    FlatLength { length: usize, width: usize },
+    InvalidSparseLine(String),
+    DuplicateCell { x: usize, y: usize },
+    UnsupportedBomb { x: usize, y: usize },
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::UnrecognisedCharacter(c) => write!(f, "Unrecognised character '{}'", c.escape_debug()),
+            SolverError::UnrecognisedInteger(s) => write!(f, "Unrecognised integer '{}'", s.escape_debug()),
+            SolverError::IrregularWidth { expected, found } => write!(f, "Irregular line width - expected {} found {}", expected, found),
+            SolverError::EmptyInput => write!(f, "Empty input"),
+            SolverError::InvalidCrop { w, h, .. } if *w == 0 || *h == 0 => write!(f, "Crop must be at least 1x1"),
+            SolverError::InvalidCrop { x, y, w, h, width, height } =>
+                write!(f, "Crop [{}, {}] {}x{} doesn't fit in the {}x{} board", x, y, w, h, width, height),
+            SolverError::DimensionMismatch { first, second } =>
+                write!(f, "Boards have different dimensions - {}x{} and {}x{}", first.0, first.1, second.0, second.1),
+            SolverError::TooManyBombs { x, y, excess } =>
+                write!(f, "Cell at position [{}, {}] has {} bombs more than it should have", x, y, excess),
+            SolverError::NotEnoughCells { x, y, required, available } =>
+                write!(f, "Cell at position [{}, {}] requires {} bomb(s) however only {} cell(s) can contain bombs", x, y, required, available),
+            SolverError::NeighboursDetermined { x, y, required } =>
+                write!(f, "Cell at position [{}, {}] requires {} more bomb(s) but all of its neighbours are already revealed or bombs", x, y, required),
+            SolverError::RegionTooManyBombs { region, excess } =>
+                write!(f, "{} has {} bombs more than its {} mine(s)", region, excess, region.max),
+            SolverError::RegionNotEnoughCells { region, required, available } =>
+                write!(f, "{} requires {} more bomb(s) however only {} cell(s) can contain bombs", region, required, available),
+            SolverError::TooManyMinesPlaced { placed, declared } =>
+                write!(f, "Placed {} bomb(s) but only {} mine(s) are declared", placed, declared),
+            SolverError::TooManyFlags { flagged, declared } =>
+                write!(f, "Placed {} flag(s) but only {} mine(s) are declared", flagged, declared),
+            SolverError::MaskLength { expected, found } =>
+                write!(f, "Mask has {} word(s) but the board needs {}", found, expected),
+            SolverError::MaskOnRevealedCell { x, y } =>
+                write!(f, "Mask places a bomb on the revealed cell at position [{}, {}]", x, y),
+            SolverError::CellOutOfBounds { x, y, width, height } =>
+                write!(f, "Cell at position [{}, {}] is outside the {}x{} board", x, y, width, height),
+            SolverError::MineOnRevealedCell { x, y } =>
+                write!(f, "Cell at position [{}, {}] is revealed so can't be a mine", x, y),
+            SolverError::ValueExceedsNeighbours { x, y, value, neighbours } =>
+                write!(f, "Cell at position [{}, {}] has the value {} but only {} neighbour(s), so at most {} is allowed", x, y, value, neighbours, neighbours),
+            SolverError::MergeConflict { x, y, first, second } =>
+                write!(f, "Cell at position [{}, {}] is '{}' in one board but '{}' in the other", x, y, first.char(), second.char()),
+            SolverError::CellNotCovered { x, y } =>
+                write!(f, "Cell at position [{}, {}] isn't covered", x, y),
+            SolverError::InvalidHeader(reason) => write!(f, "Invalid header - {}", reason),
+            SolverError::InvalidLegend(reason) => write!(f, "Invalid legend - {}", reason),
+            SolverError::InvalidRange(range) =>
+                write!(f, "Invalid range '{}' - expected two numbers from 0 to 8 like '(1-2)'", range.escape_debug()),
+            SolverError::HeaderSizeMismatch { declared, found } =>
+                write!(f, "Header declares a {}x{} board but the grid is {}x{}", declared.0, declared.1, found.0, found.1),
+            SolverError::FlatLength { length, width } =>
+                write!(f, "{} cell(s) can't be split into rows of {}", length, width),
+            SolverError::InvalidSparseLine(line) =>
+                write!(f, "Invalid line '{}' - expected 'dimensions W H' first, then 'X,Y,CELL' lines", line.escape_debug()),
+            SolverError::DuplicateCell { x, y } =>
+                write!(f, "Cell at position [{}, {}] is listed more than once", x, y),
+            SolverError::UnsupportedBomb { x, y } =>
+                write!(f, "Bomb at position [{}, {}] has no numbered neighbour to confirm it", x, y),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+#[derive(Debug, Copy, Clone)]
+enum CellTypes {
+    Covered,
+    Bomb,
+    Value(u8),
+    /// Revealed cell whose number isn't known, so it doesn't constrain its neighbours
+    UnknownValue,
+    /// Not part of the board, for variant boards that aren't rectangular. Never holds a bomb.
+    Absent,
+    /// Covered cell the player flagged as a bomb. Solved exactly like any other covered cell, as
+    /// the flag may be wrong, but kept flagged in the output.
+    Flagged,
+    /// Number only known to be between `min` and `max` inclusive, such as an unclear OCR read.
+    /// Only the search uses the range - static analysis treats it like an unknown value.
+    ValueRange { min: u8, max: u8 },
+}
+
+impl CellTypes {
+    pub fn from_char(input: char) -> Result<CellTypes, SolverError> {
+        match input {
+            '-' => Ok(CellTypes::Value(0)),
+            '?' => Ok(CellTypes::Covered),
+            'F' => Ok(CellTypes::Flagged),
+            'X' | 'x' => Ok(CellTypes::Bomb),
+            '~' => Ok(CellTypes::UnknownValue),
+            '1' => Ok(CellTypes::Value(1)),
+            '2' => Ok(CellTypes::Value(2)),
+            '3' => Ok(CellTypes::Value(3)),
+            '4' => Ok(CellTypes::Value(4)),
+            '5' => Ok(CellTypes::Value(5)),
+            '6' => Ok(CellTypes::Value(6)),
+            '7' => Ok(CellTypes::Value(7)),
+            '8' => Ok(CellTypes::Value(8)),
+            c => Err(SolverError::UnrecognisedCharacter(c)),
+        }
+    }
+
+    /// Integer form of `from_char` for generated input - -1 covered, -2 bomb, -3 unknown value and
+    /// 0 to 8 for numbers
+    pub fn from_int(input: &str) -> Result<CellTypes, SolverError> {
+        match input.parse::<i8>() {
+            Ok(-1) => Ok(CellTypes::Covered),
+            Ok(-2) => Ok(CellTypes::Bomb),
+            Ok(-3) => Ok(CellTypes::UnknownValue),
+            Ok(v @ 0..=8) => Ok(CellTypes::Value(v as u8)),
+            _ => Err(SolverError::UnrecognisedInteger(input.to_string())),
+        }
+    }
+
+    pub fn char(&self) -> char {
+        /*match &self {
+            CellTypes::Covered => '◼',
+            CellTypes::Bomb => 'x',
+            CellTypes::Value(v) =>  {
+                if *v == 0 {
+                    '◻'
+                }
+                else {
+                    v.to_string().chars().next().unwrap()
+                }
+            }
+        }*/
+        match &self {
+            CellTypes::Covered => '?',
+            CellTypes::Flagged => 'F',
+            // Grids only have room for one character, see `text` for the range
+            CellTypes::ValueRange { .. } => '~',
+            CellTypes::Bomb => 'x',
+            CellTypes::UnknownValue => '~',
+            CellTypes::Absent => ' ',
+            CellTypes::Value(v) =>  {
+                if *v == 0 {
+                    '-'
+                }
+                else {
+                    v.to_string().chars().next().unwrap()
+                }
+            }
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match &self {
+            CellTypes::Value(v) => *v,
+            CellTypes::Covered => 9,
+            CellTypes::Bomb => 10,
+            CellTypes::UnknownValue => 11,
+            CellTypes::Absent => 12,
+            CellTypes::Flagged => 13,
+            CellTypes::ValueRange { .. } => 14,
+        }
+    }
+
+    /// `char`, except ranges are written out in full as they're parsed, like '(1-2)'
+    pub fn text(&self) -> String {
+        match self {
+            CellTypes::ValueRange { min, max } => format!("({min}-{max})"),
+            cell => cell.char().to_string(),
+        }
+    }
+
+    /// Parses the inside of a bracketed range cell, like '1-2'
+    pub fn from_range(input: &str) -> Result<CellTypes, SolverError> {
+        let invalid = || SolverError::InvalidRange(format!("({input})"));
+        let (min, max) = input.split_once('-').ok_or_else(invalid)?;
+        match (min.trim().parse::<u8>(), max.trim().parse::<u8>()) {
+            (Ok(min), Ok(max)) if min == max && max <= 8 => Ok(CellTypes::Value(min)),
+            (Ok(min), Ok(max)) if min < max && max <= 8 => Ok(CellTypes::ValueRange { min, max }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The least and most bombs a numbered cell can have around it
+    pub fn value_range(&self) -> Option<(u8, u8)> {
+        match *self {
+            CellTypes::Value(v) => Some((v, v)),
+            CellTypes::ValueRange { min, max } => Some((min, max)),
+            _ => None,
+        }
+    }
+
+    /// Whether the cell is covered, flagged or not
+    pub fn is_covered(&self) -> bool {
+        matches!(self, CellTypes::Covered | CellTypes::Flagged)
+    }
+}
+
+/// Number of distinct `CellTypes::id` values
+const CELL_IDS: usize = 15;
+
+/// Start of the optional first line describing a board file, such as
+/// '# minesweeper-board v1 25x16 mines=40'
+const HEADER: &str = "# minesweeper-board";
+
+/// Start of the optional line giving a board file's own symbols, such as
+/// 'legend: covered=. bomb=* val0=_'. It comes first, or straight after the header.
+const LEGEND: &str = "legend:";
+
+/// What a board file's header line declares, each part being optional after the version
+#[derive(Debug, Copy, Clone)]
+struct BoardHeader {
+    size: Option<(usize, usize)>,
+    mines: Option<usize>,
+}
+
+impl BoardHeader {
+    pub fn parse(line: &str) -> Result<BoardHeader, SolverError> {
+        let invalid = |reason: &str| SolverError::InvalidHeader(format!("{reason} in '{line}'"));
+        let mut parts = line[HEADER.len()..].split_whitespace();
+        match parts.next() {
+            Some("v1") => {}
+            Some(version) => return Err(invalid(&format!("Unsupported version '{version}'"))),
+            None => return Err(invalid("Missing version")),
+        }
+
+        let mut header = BoardHeader { size: None, mines: None };
+        for part in parts {
+            if let Some(mines) = part.strip_prefix("mines=") {
+                header.mines = Some(mines.parse().map_err(|_| invalid(&format!("'{mines}' is not a valid number of mines")))?);
+            }
+            else if let Some((w, h)) = part.split_once('x') {
+                match (w.parse(), h.parse()) {
+                    (Ok(w), Ok(h)) => header.size = Some((w, h)),
+                    _ => return Err(invalid(&format!("'{part}' is not a valid size"))),
+                }
+            }
+            else {
+                return Err(invalid(&format!("Unrecognised '{part}'")));
+            }
+        }
+
+        Ok(header)
+    }
+}
+
+/// Position and original character of a cell replaced by lenient parsing
+type Substitution = (usize, usize, char);
+
+/// Positions of cells proven safe and of cells proven bombs
+type ForcedCells = (Vec<(usize, usize)>, Vec<(usize, usize)>);
+
+/// Name of the rule behind a step of static analysis, the cells it set and, when asked for, why
+/// each of them was set
+type Deduction = (&'static str, Vec<(usize, usize, CellTypes)>, Vec<Reason>);
+
+/// Which numbers forced a deduced cell
+#[derive(Debug, Clone)]
+enum Reason {
+    /// The number at `number` already had all of its mines, `mines`, around it
+    Satisfied { number: (usize, usize), value: u8, mines: Vec<(usize, usize)> },
+    /// The number at `number` had only `mines` left to hold its mines
+    Filled { number: (usize, usize), value: u8, mines: Vec<(usize, usize)> },
+    /// The rule combined what the numbers around the cell require - or, with none, something
+    /// other than numbers such as a mine count
+    Combined(Vec<(usize, usize)>),
+}
+
+impl Reason {
+    /// Describes the reason for the output of `--explain`
+    pub fn describe(&self, rule: &str) -> String {
+        let cells = |cells: &[(usize, usize)]| cells.iter().map(|(x, y)| format!("[{x}, {y}]")).join(", ");
+        match self {
+            Reason::Satisfied { number: (x, y), value, mines } =>
+                format!("forced by the number at [{x}, {y}] whose {value} mine(s) are satisfied by {}", cells(mines)),
+            Reason::Filled { number: (x, y), value, mines } =>
+                format!("forced by the number at [{x}, {y}] whose {value} mine(s) can only be {}", cells(mines)),
+            Reason::Combined(numbers) if numbers.is_empty() => format!("forced by {rule}"),
+            Reason::Combined(numbers) => format!("forced by {rule} of the numbers at {}", cells(numbers)),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonObject {
+        let cells = |cells: &[(usize, usize)]| cells.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec();
+        match self {
+            Reason::Satisfied { number, mines, .. } => JsonObject::new().field("reason", "satisfied").field("numbers", &cells(&[*number])).field("mines", &cells(mines)),
+            Reason::Filled { number, mines, .. } => JsonObject::new().field("reason", "filled").field("numbers", &cells(&[*number])).field("mines", &cells(mines)),
+            Reason::Combined(numbers) => JsonObject::new().field("reason", "combined").field("numbers", &cells(numbers)),
+        }
+    }
+}
+
+/// How to read a board from text
+#[derive(Debug, Copy, Clone, Default)]
+struct ParseOptions {
+    legend: Legend,
+    /// Replace unrecognised characters with `CellTypes::UnknownValue` instead of failing
+    lenient: bool,
+    /// Read each row as whitespace separated integers, see `CellTypes::from_int`
+    ints: bool,
+    /// Pad rows shorter than the longest with `CellTypes::Absent` instead of failing, also reading
+    /// spaces as absent cells
+    ragged: bool,
+    /// Which neighbours numbers count, deciding the largest number each cell can have
+    adjacency: Adjacency,
+    /// Leave numbers larger than their count of neighbours for `Board::clamp_values` instead of failing
+    clamp: bool,
+}
+
+/// Characters standing for covered cells, known bombs and empty cells, accepted on top of the
+/// default characters
+#[derive(Debug, Copy, Clone)]
+struct Legend {
+    covered: char,
+    bomb: char,
+    empty: char,
+}
+
+impl Default for Legend {
+    fn default() -> Legend {
+        Legend {
+            covered: '?',
+            bomb: 'X',
+            empty: '-',
+        }
+    }
+}
+
+impl Legend {
+    pub fn cell(&self, c: char) -> Result<CellTypes, SolverError> {
+        if c == self.covered {
+            Ok(CellTypes::Covered)
+        }
+        else if c == self.bomb {
+            Ok(CellTypes::Bomb)
+        }
+        else if c == self.empty {
+            Ok(CellTypes::Value(0))
+        }
+        else {
+            CellTypes::from_char(c)
+        }
+    }
+
+    /// Guesses the legend from the symbols used in `input`. Returns `None` if any symbol is unknown
+    /// or if more than one candidate symbol for the same cell type appears.
+    pub fn detect(input: &str) -> Option<Legend> {
+        let symbols: HashSet<char> = input.lines()
+            .filter(|line| !line.starts_with(HEADER) && !line.starts_with(LEGEND))
+            .flat_map(str::chars)
+            .filter(|c| !c.is_whitespace() && !('1'..='8').contains(c))
+            .map(|c| if c == 'x' { 'X' } else { c })
+            .collect();
+
+        let pick = |candidates: &[char], default: char| {
+            match candidates.iter().filter(|c| symbols.contains(c)).collect_vec()[..] {
+                [] => Some(default),
+                [c] => Some(*c),
+                _ => None,
+            }
+        };
+
+        let legend = Legend {
+            covered: pick(&['?', '#'], '?')?,
+            bomb: pick(&['X', '*'], 'X')?,
+            empty: pick(&['-', '.', '_', '0'], '-')?,
+        };
+
+        if symbols.iter().any(|&c| c != legend.covered && c != legend.bomb && c != legend.empty && CellTypes::from_char(c).is_err()) {
+            return None;
+        }
+
+        Some(legend)
+    }
+
+    /// Parses a legend line, which has to give a symbol for each of covered cells, bombs and
+    /// empty cells without two of them sharing one
+    pub fn parse(line: &str) -> Result<Legend, SolverError> {
+        let invalid = |reason: &str| SolverError::InvalidLegend(format!("{reason} in '{line}'"));
+        let (mut covered, mut bomb, mut empty) = (None, None, None);
+        for part in line[LEGEND.len()..].split_whitespace() {
+            let Some((name, symbol)) = part.split_once('=') else {
+                return Err(invalid(&format!("Expected name=symbol, found '{part}'")));
+            };
+            let mut chars = symbol.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return Err(invalid(&format!("'{symbol}' isn't a single character")));
+            };
+            if ('1'..='8').contains(&c) || c == '(' || c == ')' {
+                return Err(invalid(&format!("'{c}' already has a meaning of its own")));
+            }
+
+            let slot = match name {
+                "covered" => &mut covered,
+                "bomb" => &mut bomb,
+                "val0" => &mut empty,
+                _ => return Err(invalid(&format!("Unrecognised '{name}'"))),
+            };
+            if slot.is_some_and(|existing| existing != c) {
+                return Err(invalid(&format!("'{name}' is given more than once")));
+            }
+            *slot = Some(c);
+        }
+
+        let (Some(covered), Some(bomb), Some(empty)) = (covered, bomb, empty) else {
+            return Err(invalid("Expected a symbol for each of covered, bomb and val0"));
+        };
+        if covered == bomb || covered == empty || bomb == empty {
+            return Err(invalid("Two cell types share a symbol"));
+        }
+
+        Ok(Legend { covered, bomb, empty })
+    }
+}
+
+impl Display for Legend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "covered '{}', bomb '{}', empty '{}'", self.covered, self.bomb, self.empty)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CellStatus {
+    Bomb,
+    Safe,
+    Ambiguous,
+}
+
+impl CellStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CellStatus::Bomb => "bomb",
+            CellStatus::Safe => "safe",
+            CellStatus::Ambiguous => "ambiguous",
+        }
+    }
+}
+
+/// How much of a board can be worked out without guessing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum DifficultyLevel {
+    /// Static analysis decides every covered cell
+    Trivial,
+    /// Searching every possibility decides every covered cell, but static analysis alone doesn't
+    Logic,
+    /// Some covered cell is a bomb in only some possibilities
+    Guess,
+}
+
+impl DifficultyLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DifficultyLevel::Trivial => "trivial",
+            DifficultyLevel::Logic => "logic",
+            DifficultyLevel::Guess => "guess",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Difficulty {
+    level: DifficultyLevel,
+    /// Covered cells proven to be a bomb or safe
+    forced: usize,
+}
+
+/// Guaranteed cells compiled from every possible board
+#[derive(Debug, Clone)]
+struct SolveResult {
+    /// How many possibilities have a bomb in each cell and how many don't
+    counts: Vec<Vec<(usize, usize)>>,
+    /// Base board overlaid with '#' (guaranteed bomb), 'O' (guaranteed safe) and '@' (best guess)
+    output: Vec<Vec<char>>,
+    bombs: usize,
+    safe: usize,
+    /// Cell most likely to be empty and its chance of being empty - only set when no cell is guaranteed
+    best_guess: Option<((usize, usize), f64)>,
+    /// Percentage of possibilities a cell must be a bomb in to be marked '!', once marked
+    likely_threshold: Option<f64>,
+    /// How many pairs of groups `mark_pairs` labelled
+    pairs: Option<usize>,
+    /// Window of the grid to print instead of all of it
+    view: Option<View>,
+    /// How guesses are chosen between ambiguous cells
+    strategy: GuessStrategy,
+    /// Numbers `mark_satisfied` found with all of their bombs placed, highlighted when printed
+    satisfied: Vec<(usize, usize)>,
+    /// Guaranteed safe cells whose number is the same in every possibility, with that number, once
+    /// `mark_predicted` finds them
+    predicted: Vec<((usize, usize), u8)>,
+    /// Best guess and the cell that's a bomb exactly when it isn't, once `find_fifty_fifty` finds them
+    fifty_fifty: Option<((usize, usize), (usize, usize))>,
+    /// Covered cells that are a bomb in some possibilities and safe in others, row by row - what's
+    /// left to guess between once every deduction is made. Cells the search skips aren't included.
+    undetermined: Vec<(usize, usize)>,
+    /// Undetermined cell most likely to be empty and that chance, once `find_ambiguous_guess` finds
+    /// it - the best guess for after the guaranteed cells are revealed
+    ambiguous_guess: Option<((usize, usize), f64)>,
+}
+
+impl SolveResult {
+    /// Marks ambiguous cells that are a bomb in at least `percentage`% of possibilities with '!'
+    pub fn mark_likely_bombs(&mut self, percentage: f64) {
+        for (y, line) in self.output.iter_mut().enumerate() {
+            for (x, c) in line.iter_mut().enumerate() {
+                let (bomb, not) = self.counts[y][x];
+                if *c == '?' && bomb > 0 && not > 0 && bomb as f64 * 100.0 >= percentage * (bomb + not) as f64 {
+                    *c = '!';
+                }
+            }
+        }
+        self.likely_threshold = Some(percentage);
+    }
+
+    /// Labels ambiguous cells that are a bomb in exactly the possibilities another group of cells
+    /// isn't. Cells that are bombs in the same possibilities share a letter, upper case on the side
+    /// found first and lower case on the other, skipping 'O' and 'X' which mean something else.
+    pub fn mark_pairs(&mut self, possibilities: &[Board]) {
+        let mut groups: HashMap<Vec<bool>, Vec<(usize, usize)>> = HashMap::new();
+        let mut order = Vec::new();
+        for (y, line) in self.output.iter().enumerate() {
+            for (x, &c) in line.iter().enumerate() {
+                if c != '?' || self.counts[y][x].0 == 0 || self.counts[y][x].1 == 0 {
+                    continue;
+                }
+
+                let pattern = possibilities.iter().map(|board| matches!(board.board[y][x], CellTypes::Bomb)).collect_vec();
+                if !groups.contains_key(&pattern) {
+                    order.push(pattern.clone());
+                }
+                groups.entry(pattern).or_default().push((x, y));
+            }
+        }
+
+        let mut labels = ('A'..='Z').filter(|&c| c != 'O' && c != 'X');
+        let mut labelled = HashSet::new();
+        let mut pairs = 0;
+        for pattern in order {
+            let opposite = pattern.iter().map(|bomb| !bomb).collect_vec();
+            if labelled.contains(&pattern) || !groups.contains_key(&opposite) {
+                continue;
+            }
+            let Some(label) = labels.next() else { break; };
+
+            for (cells, label) in [(&groups[&pattern], label), (&groups[&opposite], label.to_ascii_lowercase())] {
+                for &(x, y) in cells {
+                    self.output[y][x] = label;
+                }
+            }
+            labelled.insert(opposite);
+            labelled.insert(pattern);
+            pairs += 1;
+        }
+        self.pairs = Some(pairs);
+    }
+
+    /// Finds the undetermined cell most likely to be empty, for when there are guaranteed cells
+    /// and so no `best_guess`. Ties go to the first cell in row major order, and flagged cells are
+    /// skipped.
+    pub fn find_ambiguous_guess(&mut self) {
+        if self.best_guess.is_some() {
+            return;
+        }
+
+        self.ambiguous_guess = self.undetermined.iter()
+            .filter(|&&(x, y)| self.output[y][x] != 'F')
+            .map(|&(x, y)| {
+                let (bomb, not) = self.counts[y][x];
+                ((x, y), not as f64 / (bomb + not) as f64)
+            })
+            .reduce(|best, cell| if cell.1 > best.1 { cell } else { best });
+    }
+
+    /// Checks whether the best guess is an unavoidable 50/50 - no covered cell is safer than even
+    /// odds and one other cell is a bomb in exactly the possibilities the guess isn't. Compiling the
+    /// result checks this already, so it only needs calling again once the best guess moves.
+    pub fn find_fifty_fifty(&mut self, possibilities: &[Board]) {
+        const EPSILON: f64 = 1e-9;
+
+        self.fifty_fifty = None;
+        let Some(((x, y), safe)) = self.best_guess else { return; };
+        let safer = self.counts.iter().flatten().any(|&(bomb, not)| not > 0 && (not as f64 / (bomb + not) as f64) > 0.5 + EPSILON);
+        if (safe - 0.5).abs() > EPSILON || safer {
+            return;
+        }
+
+        let is_bomb = |board: &Board, (x, y): (usize, usize)| matches!(board.board[y][x], CellTypes::Bomb);
+        self.fifty_fifty = (0..self.counts.len())
+            .flat_map(|oy| (0..self.counts[oy].len()).map(move |ox| (ox, oy)))
+            .filter(|&cell| cell != (x, y) && matches!(self.output[cell.1][cell.0], '?' | 'F'))
+            .find(|&cell| possibilities.iter().all(|board| is_bomb(board, cell) != is_bomb(board, (x, y))))
+            .map(|cell| ((x, y), cell));
+    }
+
+    /// Finds the numbers of `base` that have as many bombs around them, counting guaranteed ones,
+    /// as their value while still touching covered cells - every one of those cells is safe
+    pub fn mark_satisfied(&mut self, base: &Board) {
+        let bombs = self.covered_cells(base).into_iter()
+            .filter(|cell| cell.2 == CellStatus::Bomb)
+            .map(|(x, y, _, _)| (x, y))
+            .collect::<HashSet<_>>();
+        let placed = base.with_mines(&bombs.iter().copied().collect_vec()).unwrap();
+
+        self.satisfied = (0..base.height).flat_map(|y| (0..base.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(placed.constraint(x, y), Some((0, covered)) if !covered.is_empty()))
+            .collect();
+    }
+
+    /// Finds the guaranteed safe cells with as many bombs around them in every one of
+    /// `possibilities`, which is the number revealing them will show. Bombs could be in any of the
+    /// `ignore`d cells the search skips, so cells next to them are never predicted.
+    pub fn mark_predicted(&mut self, possibilities: &[Board], ignore: &HashSet<(usize, usize)>) {
+        let Some(first) = possibilities.first() else { return; };
+        let bombs_around = |board: &Board, x: usize, y: usize| {
+            board.neighbours(x, y).filter(|&(x, y)| matches!(board.board[y][x], CellTypes::Bomb)).count()
+        };
+
+        self.predicted = (0..first.height).flat_map(|y| (0..first.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.output[y][x] == 'O' && !first.neighbours(x, y).any(|cell| ignore.contains(&cell)))
+            .filter_map(|(x, y)| {
+                let value = bombs_around(first, x, y);
+                possibilities.iter().all(|board| bombs_around(board, x, y) == value).then_some(((x, y), value as u8))
+            })
+            .collect();
+    }
+
+    pub fn grid_string(&self) -> String {
+        self.output.iter().map(|line| line.iter().collect::<String>()).join("\n")
+    }
+
+    /// Picks guesses with `strategy` from now on, moving the '@' of the best guess to match
+    pub fn use_strategy(&mut self, base: &Board, strategy: GuessStrategy) {
+        self.strategy = strategy;
+        let Some(((x, y), _)) = self.best_guess else { return; };
+        let Some(guess) = self.recommended_move(base) else { return; };
+
+        self.output[y][x] = base.board[y][x].char();
+        debug_assert!(self.output[guess.0.1][guess.0.0] == '?', "best guess moved onto a cell that isn't ambiguous or is flagged");
+        self.output[guess.0.1][guess.0.0] = '@';
+        self.best_guess = Some(guess);
+    }
+
+    /// A guaranteed safe cell covered in `base` if there is one, otherwise the covered cell the
+    /// guess strategy scores highest, with its chance of being safe. Flagged cells are only
+    /// recommended when they're guaranteed safe, never as a guess.
+    pub fn recommended_move(&self, base: &Board) -> Option<((usize, usize), f64)> {
+        let score = |(x, y): (usize, usize), safe: f64| match self.strategy {
+            GuessStrategy::Safest => safe,
+            GuessStrategy::ConstraintCoverage => safe * base.neighbours(x, y)
+                .filter(|&(x, y)| base.constraint(x, y).is_some_and(|(_, covered)| !covered.is_empty()))
+                .count() as f64,
+        };
+
+        self.covered_cells(base).into_iter()
+            .filter_map(|(x, y, _, probability)| probability.map(|probability| ((x, y), 1.0 - probability)))
+            .filter(|&((x, y), safe)| safe >= 1.0 || safe > 0.0 && !matches!(base.board[y][x], CellTypes::Flagged))
+            .map(|(cell, safe)| (cell, safe, (safe >= 1.0, score(cell, safe))))
+            .reduce(|best, cell| if best.2 >= cell.2 { best } else { cell })
+            .map(|(cell, safe, _)| (cell, safe))
+    }
+
+    /// Status of every cell covered in `base`, row by row, with its chance of being a bomb when
+    /// that's known. Cells no number touches are never counted so have no probability.
+    pub fn covered_cells(&self, base: &Board) -> Vec<(usize, usize, CellStatus, Option<f64>)> {
+        base.covered_cells().map(|(x, y)| {
+            let (bomb, not) = self.counts[y][x];
+            let status = match (bomb, not) {
+                (b, 0) if b > 0 => CellStatus::Bomb,
+                (0, n) if n > 0 => CellStatus::Safe,
+                _ => CellStatus::Ambiguous,
+            };
+            let probability = (bomb + not > 0).then(|| bomb as f64 / (bomb + not) as f64);
+
+            (x, y, status, probability)
+        }).collect()
+    }
+
+    /// Guaranteed safe cells to click and guaranteed bombs to flag, each row by row
+    pub fn actions(&self, base: &Board) -> ForcedCells {
+        let cells = self.covered_cells(base);
+        let with = |status: CellStatus| cells.iter().filter(|cell| cell.2 == status).map(|&(x, y, _, _)| (x, y)).collect_vec();
+        (with(CellStatus::Safe), with(CellStatus::Bomb))
+    }
+
+    /// `base` with every guaranteed bomb placed, leaving guaranteed safe cells covered so the
+    /// result can be solved again once they're revealed
+    pub fn applied(&self, base: &Board) -> Board {
+        let mut board = base.clone();
+        for (x, y, status, _) in self.covered_cells(base) {
+            if status == CellStatus::Bomb {
+                board.board[y][x] = CellTypes::Bomb;
+            }
+        }
+        board
+    }
+
+    /// Exact chance of the cell being a bomb as a fraction in lowest terms, if any possibility counted it
+    pub fn bomb_fraction(&self, x: usize, y: usize) -> Option<(u128, u128)> {
+        let (bomb, not) = self.counts[y][x];
+        let (numerator, denominator) = (bomb as u128, (bomb + not) as u128);
+        if denominator == 0 {
+            return None;
+        }
+
+        let (mut a, mut b) = (numerator, denominator);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        Some((numerator / a, denominator / a))
+    }
+
+    /// `covered_cells` as JSON objects, with the exact bomb fraction of each when `fractions` is set
+    pub fn covered_json(&self, base: &Board, fractions: bool) -> Vec<JsonObject> {
+        self.covered_cells(base).into_iter().map(|(x, y, status, probability)| {
+            let object = JsonObject::new()
+                .field("x", &x)
+                .field("y", &y)
+                .field("status", status.name())
+                .field("bomb_probability", &probability);
+            if fractions {
+                object.field("bomb_fraction", &self.bomb_fraction(x, y).map(|(n, d)| vec![n, d]))
+            } else {
+                object
+            }
+        }).collect()
+    }
+
+    pub fn to_json(&self, base: &Board, possibilities: &[Board], show_possibilities: bool) -> JsonObject {
+        let best_guess = self.best_guess.map(|((x, y), probability)| JsonObject::new()
+            .field("x", &x)
+            .field("y", &y)
+            .field("safe_probability", &probability));
+        let numbers = (0..base.height).flat_map(|y| (0..base.width).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let number = JsonObject::new().field("x", &x).field("y", &y);
+                match base.board[y][x] {
+                    CellTypes::Value(value) => Some(number.field("value", &(value as usize))),
+                    // Ranges give their bounds in place of a single value
+                    CellTypes::ValueRange { min, max } => Some(number.field("value", &JsonObject::new()
+                        .field("min", &(min as usize))
+                        .field("max", &(max as usize)))),
+                    _ => None,
+                }
+            })
+            .collect_vec();
+
+        let mut object = JsonObject::new()
+            .field("input", &base.json_summary())
+            .field("solved", &false)
+            .field("possibilities", &possibilities.len())
+            .field("guaranteed", &self.grid_string())
+            .field("bombs", &self.bombs)
+            .field("safe", &self.safe)
+            .field("best_guess", &best_guess)
+            .field("undetermined", &self.undetermined.iter().map(|&(x, y)| JsonObject::new().field("x", &x).field("y", &y)).collect_vec())
+            .field("fifty_fifty", &self.fifty_fifty.map(|cells| [cells.0, cells.1].map(|(x, y)| JsonObject::new().field("x", &x).field("y", &y)).to_vec()))
+            .field("cells", &self.covered_json(base, false))
+            .field("numbers", &numbers)
+            // Every search is exhaustive, so the probabilities are exact
+            .field("exact", &true);
+
+        if show_possibilities {
+            object = object.field("boards", &possibilities.iter().map(|board| board.to_string()).collect_vec());
+        }
+
+        object
+    }
+}
+
+/// The formatter's precision sets the decimal places of the best guess' chance, defaulting to 2
+impl Display for SolveResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let output_string = match &self.view {
+            Some(view) => view.render(&self.output),
+            None if !self.satisfied.is_empty() || !self.predicted.is_empty() => self.output.iter().enumerate().map(|(y, line)| line.iter().enumerate().map(|(x, c)| {
+                if self.satisfied.contains(&(x, y)) { return format!("{HIGHLIGHT}{c}{RESET}"); }
+                match self.predicted.iter().find(|(cell, _)| *cell == (x, y)) {
+                    Some(&(_, value)) => format!("{PREDICTED}{}{RESET}", CellTypes::Value(value).char()),
+                    None => c.to_string(),
+                }
+            }).collect::<String>()).join("\n"),
+            None => self.grid_string(),
+        };
+        let satisfied = "Highlighted numbers have all of their bombs found, so their other covered neighbours are safe";
+        let precision = f.precision().unwrap_or(2);
+
+        if let Some((_, probability)) = self.best_guess {
+            match self.fifty_fifty {
+                Some(((x1, y1), (x2, y2))) => write!(f, "{output_string}\n\nUnavoidable 50/50 between ({x1},{y1}) and ({x2},{y2}) - Exactly one of them is a bomb and no cell is safer to reveal\n")?,
+                None => write!(f, "{output_string}\n\nCell marked '@' is most likely to be empty with a chance of {:.*}% - No cells are definitively a bomb or safe\n", precision, (probability * 100.0))?,
+            }
+            writeln!(f, "{} cell(s) can only be decided by guessing: {}", self.undetermined.len(), self.undetermined.iter().map(|(x, y)| format!("[{x}, {y}]")).join(", "))?;
+            if let Some(threshold) = self.likely_threshold {
+                writeln!(f, "Cells marked '!' are a bomb in at least {threshold}% of possibilities")?;
+            }
+            if self.pairs.is_some_and(|pairs| pairs > 0) {
+                writeln!(f, "Either every cell marked with an upper case letter is a bomb or every cell with the lower case one is, never both")?;
+            }
+            if !self.satisfied.is_empty() {
+                writeln!(f, "{satisfied}")?;
+            }
+            Ok(())
+        }
+        else {
+            write!(f, "Guaranteed cells:\n{output_string}\n\nFound {} guaranteed bomb(s), {} guaranteed safe cell(s)\n", self.bombs, self.safe)?;
+            if let Some(((x, y), probability)) = self.ambiguous_guess {
+                writeln!(f, "Of the ambiguous cells, [{x}, {y}] is most likely to be empty with a chance of {:.*}%", precision, probability * 100.0)?;
+            }
+            write!(f, "\nKey:\n\t'#' - Guaranteed bomb\n\t'O' - Guaranteed safe\n")?;
+            if let Some(threshold) = self.likely_threshold {
+                writeln!(f, "\t'!' - Bomb in at least {threshold}% of possibilities")?;
+            }
+            if self.pairs.is_some_and(|pairs| pairs > 0) {
+                writeln!(f, "\t'A' / 'a' - Either every 'A' is a bomb or every 'a' is, never both")?;
+            }
+            if !self.predicted.is_empty() {
+                writeln!(f, "\t{PREDICTED}1{RESET} - Guaranteed safe, revealing the number shown")?;
+            }
+            if !self.satisfied.is_empty() {
+                writeln!(f, "\n{satisfied}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Window onto a grid too large for the terminal
+#[derive(Debug, Copy, Clone)]
+struct View {
+    /// Cell to centre the window on, as far as the edges of the grid allow
+    centre: (usize, usize),
+    /// Columns and lines available, including the rulers
+    size: (usize, usize),
+}
+
+impl View {
+    /// Size of the terminal from `$COLUMNS` and `$LINES`, which most shells only set for
+    /// themselves, falling back to 80x24
+    pub fn terminal_size() -> (usize, usize) {
+        let from_env = |name: &str, default: usize| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        (from_env("COLUMNS", 80), from_env("LINES", 24))
+    }
+
+    /// The window's part of `grid`, with x coordinates above it - the tens every tenth column over
+    /// the units - and y coordinates to its left
+    #[allow(clippy::needless_range_loop)]
+    pub fn render(&self, grid: &[Vec<char>]) -> String {
+        let (width, height) = (grid.first().map_or(0, |line| line.len()), grid.len());
+        if width == 0 {
+            return String::new();
+        }
+
+        let label = (height - 1).to_string().len();
+        let columns = self.size.0.saturating_sub(label + 1).clamp(1, width);
+        let lines = self.size.1.saturating_sub(2).clamp(1, height);
+        let x0 = self.centre.0.saturating_sub(columns / 2).min(width - columns);
+        let y0 = self.centre.1.saturating_sub(lines / 2).min(height - lines);
+        let margin = " ".repeat(label + 1);
+
+        let mut tens = vec![' '; columns];
+        for x in (x0..x0 + columns).filter(|x| x % 10 == 0) {
+            for (i, c) in (x / 10).to_string().chars().enumerate() {
+                if let Some(slot) = tens.get_mut(x - x0 + i) {
+                    *slot = c;
+                }
+            }
+        }
+        let units = (x0..x0 + columns).map(|x| char::from(b'0' + (x % 10) as u8)).collect::<String>();
+
+        let mut output = format!("{margin}{}\n{margin}{units}", tens.iter().collect::<String>().trim_end());
+        for y in y0..y0 + lines {
+            output.push_str(&format!("\n{y:>label$} {}", grid[y][x0..x0 + columns].iter().collect::<String>()));
+        }
+        output
+    }
+}
+
+/// Durations of each phase of a run, printed to stderr when dropped if enabled so that every exit
+/// path reports the phases it got through
+#[derive(Debug, Clone)]
+struct Timings {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Timings {
+        Timings {
+            enabled,
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, phase: &'static str, start: Instant) {
+        self.phases.push((phase, start.elapsed()));
+    }
+
+    /// The phases recorded so far as milliseconds by name
+    pub fn to_json(&self) -> JsonObject {
+        self.phases.iter().fold(JsonObject::new(), |object, (phase, duration)| object.field(phase, &(duration.as_secs_f64() * 1000.0)))
+    }
+}
+
+impl Drop for Timings {
+    fn drop(&mut self) {
+        if !self.enabled || self.phases.is_empty() {
+            return;
+        }
+
+        eprintln!("Timings:");
+        for (phase, duration) in &self.phases {
+            eprintln!("\t{phase}: {:.3}ms", duration.as_secs_f64() * 1000.0);
+        }
+    }
+}
+
+/// Counts from the search for `--metrics`, written to stderr along with the phase timings when dropped
+/// so that runs ending before the search report them too, as zeros
+#[derive(Debug, Clone)]
+struct Metrics {
+    format: Option<MetricsFormat>,
+    timings: Timings,
+    peak_open_boards: usize,
+    visited: usize,
+    possibilities: usize,
+    regions: usize,
+}
+
+impl Metrics {
+    pub fn new(format: Option<MetricsFormat>, timings: Timings) -> Metrics {
+        Metrics {
+            format,
+            timings,
+            peak_open_boards: 0,
+            visited: 0,
+            possibilities: 0,
+            regions: 0,
+        }
+    }
+}
+
+impl Drop for Metrics {
+    fn drop(&mut self) {
+        if self.format != Some(MetricsFormat::Json) {
+            return;
+        }
+
+        eprintln!("{}", JsonObject::new()
+            .field("timings_ms", &self.timings.to_json())
+            .field("peak_open_boards", &self.peak_open_boards)
+            .field("visited", &self.visited)
+            .field("possibilities", &self.possibilities)
+            .field("regions", &self.regions)
+            .to_json());
+    }
+}
+
+/// Order of magnitude of a search, from `Board::estimated_search_size`
+#[derive(Debug, Copy, Clone)]
+struct SearchSize {
+    /// Covered cells the search decides
+    cells: usize,
+    largest_region: usize,
+}
+
+/// Which of the eight surrounding cells count as neighbours
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+enum Adjacency {
+    /// All eight, as in normal minesweeper
+    #[default]
+    King,
+    /// Only the four sharing an edge
+    Orthogonal,
+    /// Only the four sharing a corner
+    Diagonal,
+}
+
+impl Adjacency {
+    pub fn offsets(&self) -> &'static [(i32, i32)] {
+        match self {
+            Adjacency::King => &[(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1)],
+            Adjacency::Orthogonal => &[(-1, 0), (0, 1), (1, 0), (0, -1)],
+            Adjacency::Diagonal => &[(-1, -1), (-1, 1), (1, 1), (1, -1)],
+        }
+    }
+}
+
+/// How to choose between ambiguous cells when there's no safe cell to reveal
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, ValueEnum)]
+enum GuessStrategy {
+    /// The cell most likely to be safe
+    #[default]
+    Safest,
+    /// The cell with the best chance of being safe times the count of unsatisfied numbers it
+    /// touches. A cheap heuristic for how much revealing it tells you, not an optimal strategy
+    ConstraintCoverage,
+}
+
+/// How `--metrics` are written
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum MetricsFormat {
+    /// A single line JSON object
+    Json,
+}
+
+/// Order the search expands open boards in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum SearchOrder {
+    /// Breadth first, in the order boards were found
+    Bfs,
+    /// Boards with the fewest bombs left to place first
+    BestFirst,
+}
+
+/// Open board ranked by how many bombs it still needs, ties going to the board pushed first
+#[derive(Debug, Clone)]
+struct RankedBoard {
+    remaining: usize,
+    order: usize,
+    board: Board,
+}
+
+impl PartialEq for RankedBoard {
+    fn eq(&self, other: &Self) -> bool {
+        (self.remaining, self.order) == (other.remaining, other.order)
+    }
+}
+
+impl Eq for RankedBoard {}
+
+impl PartialOrd for RankedBoard {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedBoard {
+    // BinaryHeap pops the greatest element, so fewer remaining (then earlier) ranks higher
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.remaining, other.order).cmp(&(self.remaining, self.order))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum OpenBoards {
+    Queue(VecDeque<Board>),
+    Ranked(BinaryHeap<RankedBoard>),
+}
+
+/// Open boards waiting to be expanded by the search. Breadth first search switches to depth first
+/// once more than `max` boards are open, as depth first keeps far fewer boards open at once.
+#[derive(Debug, Clone)]
+struct Frontier {
+    open: OpenBoards,
+    max: Option<usize>,
+    depth_first: bool,
+    pushed: usize,
+    /// Most boards open at once
+    peak: usize,
+}
+
+impl Frontier {
+    pub fn new(order: SearchOrder, max: Option<usize>) -> Frontier {
+        Frontier {
+            open: match order {
+                SearchOrder::Bfs => OpenBoards::Queue(VecDeque::new()),
+                SearchOrder::BestFirst => OpenBoards::Ranked(BinaryHeap::new()),
+            },
+            max,
+            depth_first: false,
+            pushed: 0,
+            peak: 0,
+        }
+    }
+
+    pub fn push(&mut self, board: Board) {
+        self.pushed += 1;
+
+        match &mut self.open {
+            OpenBoards::Queue(open) => {
+                open.push_back(board);
+
+                if !self.depth_first && self.max.is_some_and(|max| open.len() > max) {
+                    eprintln!("Warning: more than {} open boards, switching to depth first search", self.max.unwrap());
+                    self.depth_first = true;
+                }
+            }
+            OpenBoards::Ranked(open) => {
+                // Open boards have already been validated
+                let remaining = board.validate_board().unwrap_or(0);
+                open.push(RankedBoard { remaining, order: self.pushed, board });
+            }
+        }
+
+        let open = match &self.open {
+            OpenBoards::Queue(open) => open.len(),
+            OpenBoards::Ranked(open) => open.len(),
+        };
+        self.peak = self.peak.max(open);
+    }
+
+    pub fn pop(&mut self) -> Option<Board> {
+        match &mut self.open {
+            OpenBoards::Queue(open) if self.depth_first => open.pop_back(),
+            OpenBoards::Queue(open) => open.pop_front(),
+            OpenBoards::Ranked(open) => open.pop().map(|ranked| ranked.board),
+        }
+    }
+}
+
+/// Counts how many live search branches (open or solved boards) have a bomb in each cell.
+/// Boards only ever gain bombs as the search goes deeper, so a cell that's a bomb in every live
+/// branch is a bomb in every possibility the search can still find.
+#[derive(Debug, Clone)]
+struct LiveBranches {
+    bombs: Vec<Vec<usize>>,
+    live: usize,
+    reported: HashSet<(usize, usize)>,
+}
+
+impl LiveBranches {
+    pub fn new(start: &Board) -> LiveBranches {
+        let mut live = LiveBranches {
+            bombs: vec![vec![0; start.width]; start.height],
+            live: 0,
+            reported: HashSet::new(),
+        };
+        live.add(start);
+        live
+    }
+
+    pub fn add(&mut self, board: &Board) {
+        self.update(board, true);
+    }
+
+    pub fn remove(&mut self, board: &Board) {
+        self.update(board, false);
+    }
+
+    fn update(&mut self, board: &Board, add: bool) {
+        for y in 0..board.height {
+            for x in 0..board.width {
+                if matches!(board.board[y][x], CellTypes::Bomb) {
+                    if add { self.bombs[y][x] += 1; } else { self.bombs[y][x] -= 1; }
+                }
+            }
+        }
+
+        if add { self.live += 1; } else { self.live -= 1; }
+    }
+
+    /// Cells covered in `start` that have become a bomb in every live branch since the last call
+    pub fn newly_forced(&mut self, start: &Board) -> Vec<(usize, usize)> {
+        let mut forced = Vec::new();
+        if self.live == 0 {
+            return forced;
+        }
+
+        for y in 0..start.height {
+            for x in 0..start.width {
+                if self.bombs[y][x] == self.live && start.board[y][x].is_covered() && self.reported.insert((x, y)) {
+                    forced.push((x, y));
+                }
+            }
+        }
+
+        forced
+    }
+}
+
+/// Lazily runs the breadth first search, yielding the bombs of each possibility as it's found
+struct MineConfigurations {
+    open: VecDeque<Board>,
+    solved: Vec<Board>,
+    visited: HashSet<u64>,
+    ignore: HashSet<(usize, usize)>,
+}
+
+impl Iterator for MineConfigurations {
+    type Item = Vec<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.solved.is_empty() {
+            let board = self.open.pop_front()?;
+            let (solved_boards, open_boards) = board.get_possible_boards(&mut self.visited, &self.ignore, &[]);
+            self.solved = solved_boards;
+            self.solved.reverse();
+            self.open.extend(open_boards);
+        }
+
+        let board = self.solved.pop()?;
+        Some((0..board.height)
+            .flat_map(|y| (0..board.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(board.board[y][x], CellTypes::Bomb))
+            .collect())
+    }
+}
+
+/// Reflection or rotation of a board, as an optional swap of x and y followed by optional flips
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Symmetry {
+    transpose: bool,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+impl Symmetry {
+    /// Every symmetry of a `width` by `height` board - swapping x and y only keeps square boards
+    /// the same shape
+    pub fn all(width: usize, height: usize) -> Vec<Symmetry> {
+        let mut symmetries = Vec::new();
+        for transpose in [false, true] {
+            if transpose && width != height {
+                continue;
+            }
+            for flip_x in [false, true] {
+                for flip_y in [false, true] {
+                    symmetries.push(Symmetry { transpose, flip_x, flip_y });
+                }
+            }
+        }
+        symmetries
+    }
+
+    /// Where the cell at (`x`, `y`) ends up on a `width` by `height` board
+    pub fn apply(&self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        let (mut x, mut y, width, height) = if self.transpose { (y, x, height, width) } else { (x, y, width, height) };
+        if self.flip_x { x = width - 1 - x; }
+        if self.flip_y { y = height - 1 - y; }
+        (x, y)
+    }
+}
+
+/// Total number of mines in a rectangle of the board
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MineCount {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    /// Fewest and most mines the rectangle can hold, inclusive
+    min: usize,
+    max: usize,
+}
+
+impl Display for MineCount {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Region [{}, {}] {}x{}", self.x, self.y, self.w, self.h)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Board {
+    board: Vec<Vec<CellTypes>>,
+    width: usize,
+    height: usize,
+    /// Known mine totals for regions of the board, each acting like a number covering its rectangle
+    mine_counts: Vec<MineCount>,
+    adjacency: Adjacency,
+    /// Neighbours of each cell under `adjacency`, indexed by `y * width + x`. Only depends on the
+    /// size and adjacency, so it's shared between every board the search clones from this one.
+    neighbour_cache: Rc<Vec<Vec<(usize, usize)>>>,
+}
+
+impl Board {
+    pub fn new(board: Vec<Vec<CellTypes>>, mine_counts: Vec<MineCount>, adjacency: Adjacency) -> Board {
+        let (width, height) = (board.first().map_or(0, Vec::len), board.len());
+        Board {
+            board,
+            width,
+            height,
+            mine_counts,
+            adjacency,
+            neighbour_cache: Rc::new(Board::neighbour_lists(width, height, adjacency)),
+        }
+    }
+
+    fn neighbour_lists(width: usize, height: usize, adjacency: Adjacency) -> Vec<Vec<(usize, usize)>> {
+        (0..height).flat_map(|y| (0..width).map(move |x| (x, y))).map(|(x, y)| {
+            adjacency.offsets().iter().filter_map(|offset| {
+                let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    return None;
+                }
+                Some((x as usize, y as usize))
+            }).collect()
+        }).collect()
+    }
+
+    /// Changes which neighbours numbers count, rebuilding the neighbour cache
+    pub fn set_adjacency(&mut self, adjacency: Adjacency) {
+        self.adjacency = adjacency;
+        self.neighbour_cache = Rc::new(Board::neighbour_lists(self.width, self.height, adjacency));
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    pub fn compile_guaranteed(base: &Board, possibilities: &[Board], ignore: &HashSet<(usize, usize)>) -> SolveResult {
+        let mut board = Vec::with_capacity(base.height);
+        for _ in 0..base.height {
+            let mut line = Vec::with_capacity(base.width);
+            for _ in 0..base.width {
+                // Bomb, Not
+                line.push((0usize, 0usize));
+            }
+            board.push(line);
+        }
+
+        for possibility in possibilities {
+            for x in 0..base.width {
+                for y in 0..base.height {
+                    if ignore.contains(&(x, y)) {
+                        continue;
+                    }
+
+                    let is_bomb = match (&possibility.board[y][x], &base.board[y][x]) {
+                        // Cells revealed as safe by static analysis
+                        (CellTypes::Value(_) | CellTypes::UnknownValue, CellTypes::Covered | CellTypes::Flagged) => false,
+                        (CellTypes::Value(_) | CellTypes::UnknownValue | CellTypes::Absent | CellTypes::ValueRange { .. }, _) => continue,
+                        (CellTypes::Bomb, _) => true,
+                        (CellTypes::Covered | CellTypes::Flagged, _) => false,
+                    };
+
+                    if is_bomb {
+                        board[y][x] = (board[y][x].0 + 1, board[y][x].1);
+                    }
+                    else {
+                        board[y][x] = (board[y][x].0, board[y][x].1 + 1);
+                    }
+                }
+            }
+        }
+
+        let mut board_probabilities = Vec::with_capacity(base.height);
+        let mut output = Vec::with_capacity(base.height);
+        let mut found = false;
+        let mut bombs = 0;
+        let mut safe = 0;
+
+        for y in 0..base.height {
+            output.push(Vec::with_capacity(base.width));
+            board_probabilities.push(Vec::with_capacity(base.width));
+            for x in 0..base.width {
+                board_probabilities[y].push(0f64);
+                if board[y][x].0 == 0 && board[y][x].1 == 0 || ignore.contains(&(x, y)) || (board[y][x].0 > 0 && board[y][x].1 == 0 && matches!(base.board[y][x], CellTypes::Bomb)) {
+                    output[y].push(base.board[y][x].char());
+                }
+                else if board[y][x].0 > 0 && board[y][x].1 == 0 {
+                    output[y].push('#');
+                    found = true;
+                    bombs += 1;
+                }
+                else if board[y][x].0 == 0 && board[y][x].1 > 0 {
+                    output[y].push('O');
+                    found = true;
+                    safe += 1;
+                }
+                else {
+                    if !found {
+                        board_probabilities[y][x] = (board[y][x].1 as f64) / (board[y][x].1 as f64 + board[y][x].0 as f64);
+                    }
+
+                    output[y].push(if matches!(base.board[y][x], CellTypes::Flagged) { 'F' } else { '?' });
+                }
+            }
+        }
+
+
+        let mut best_guess = None;
+        if !found {
+            // Only ambiguous cells some possibility decides are candidates - every other cell keeps its
+            // probability of 0. Flagged cells are left alone, as the player already thinks they're bombs
+            let mut max: Option<(f64, (usize, usize))> = None;
+            for y in 0..base.height {
+                for x in 0..base.width {
+                    let decided = board[y][x].0 + board[y][x].1 > 0;
+                    if decided && output[y][x] == '?' && (max.is_none() || board_probabilities[y][x] > max.unwrap().0) {
+                        max = Some((board_probabilities[y][x], (x, y)));
+                    }
+                }
+            }
+
+            if let Some((probability, (x, y))) = max {
+                debug_assert!(base.board[y][x].is_covered(), "best guess at [{x}, {y}] isn't a covered cell");
+                output[y][x] = '@';
+                best_guess = Some(((x, y), probability));
+            }
+        }
+
+        let undetermined = (0..base.height).flat_map(|y| (0..base.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| board[y][x].0 > 0 && board[y][x].1 > 0)
+            .collect();
+
+        let mut result = SolveResult {
+            counts: board,
+            output,
+            bombs,
+            safe,
+            best_guess,
+            likely_threshold: None,
+            pairs: None,
+            view: None,
+            strategy: GuessStrategy::Safest,
+            satisfied: Vec::new(),
+            predicted: Vec::new(),
+            fifty_fifty: None,
+            undetermined,
+            ambiguous_guess: None,
+        };
+        result.find_fifty_fifty(possibilities);
+        result
+    }
+
+    pub fn from_string(input: String) -> Result<Board, SolverError> {
+        Board::from_string_with(input, &ParseOptions::default()).map(|(board, _)| board)
+    }
+
+    /// Parses a board, also returning the position of every unrecognised character that lenient
+    /// parsing replaced with an unknown value.
+    ///
+    /// Empty lines are skipped wherever they are, so rows either side of a blank line are joined
+    /// into one board - `replay` and `batch` split files of several boards before parsing each. A
+    /// line of only spaces isn't empty, so fails to parse unless `ragged` reads them as absent cells.
+    /// The y coordinates of substitutions count only the rows kept. A `LEGEND` line replaces
+    /// the legend in `options` for this input.
+    pub fn from_string_with(input: String, options: &ParseOptions) -> Result<(Board, Vec<Substitution>), SolverError> {
+        let mut board = Vec::new();
+        let mut substitutions = Vec::new();
+        let mut width = None;
+
+        let mut lines = input.lines().filter(|line| !line.is_empty()).peekable();
+        let header = match lines.peek() {
+            Some(line) if line.starts_with(HEADER) => Some(BoardHeader::parse(lines.next().unwrap())?),
+            _ => None,
+        };
+        // A legend in the file takes over from the one in `options`
+        let legend = match lines.peek() {
+            Some(line) if line.starts_with(LEGEND) => Legend::parse(lines.next().unwrap())?,
+            _ => options.legend,
+        };
+
+        for line_str in lines {
+            if line_str.is_empty() || options.ints && line_str.trim().is_empty() {
+                continue;
+            }
+            let line = if options.ints {
+                line_str.split_whitespace().map(CellTypes::from_int).collect::<Result<Vec<_>, _>>()?
+            } else {
+                let mut line = Vec::new();
+                let mut chars = line_str.chars();
+                while let Some(c) = chars.next() {
+                    if c == '(' {
+                        let mut range = String::new();
+                        loop {
+                            match chars.next() {
+                                Some(')') => break,
+                                Some(c) => range.push(c),
+                                None => return Err(SolverError::InvalidRange(format!("({range}"))),
+                            }
+                        }
+                        line.push(CellTypes::from_range(&range)?);
+                        continue;
+                    }
+
+                    match legend.cell(c) {
+                        Ok(cell) => line.push(cell),
+                        Err(_) if options.ragged && c == ' ' => line.push(CellTypes::Absent),
+                        Err(SolverError::UnrecognisedCharacter(c)) if options.lenient => {
+                            substitutions.push((line.len(), board.len(), c));
+                            line.push(CellTypes::UnknownValue);
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                line
+            };
+
+            let found = line.len();
+            match width {
+                None => width = Some(found),
+                Some(current) if options.ragged => width = Some(found.max(current)),
+                Some(width) if width != found => {
+                    return Err(SolverError::IrregularWidth { expected: width, found });
+                }
+                _ => {}
+            }
+
+            board.push(line);
+        }
+
+        if board.is_empty() {
+            return Err(SolverError::EmptyInput);
+        }
+
+        let width = width.unwrap();
+        let height = board.len();
+        for line in &mut board {
+            line.resize(width, CellTypes::Absent);
+        }
+
+        let mut mine_counts = Vec::new();
+        if let Some(header) = header {
+            if let Some(declared) = header.size.filter(|&size| size != (width, height)) {
+                return Err(SolverError::HeaderSizeMismatch { declared, found: (width, height) });
+            }
+            if let Some(mines) = header.mines {
+                mine_counts.push(MineCount { x: 0, y: 0, w: width, h: height, min: mines, max: mines });
+            }
+        }
+
+        let board = Board::new(board, mine_counts, options.adjacency);
+
+        if !options.clamp {
+            for y in 0..board.height {
+                for x in 0..board.width {
+                    board.check_value(x, y)?;
+                }
+            }
+        }
+
+        Ok((board, substitutions))
+    }
+
+    /// Parses a board written as one string of cell characters, row after row, `width` cells to
+    /// a row
+    pub fn from_flat(cells: &str, width: usize) -> Result<Board, SolverError> {
+        let cells = cells.chars().map(CellTypes::from_char).collect::<Result<Vec<_>, _>>()?;
+        if cells.is_empty() {
+            return Err(SolverError::EmptyInput);
+        }
+        if width == 0 || cells.len() % width != 0 {
+            return Err(SolverError::FlatLength { length: cells.len(), width });
+        }
+
+        let board = cells.chunks(width).map(<[CellTypes]>::to_vec).collect_vec();
+        Ok(Board::new(board, Vec::new(), Adjacency::default()))
+    }
+
+    /// Parses a board from a 'dimensions W H' line and an 'X,Y,CELL' line for each cell that
+    /// isn't covered, leaving every other cell covered. CELL is a cell character or a bracketed range.
+    pub fn from_sparse(input: &str) -> Result<Board, SolverError> {
+        let mut lines = input.lines().map(str::trim).filter(|line| !line.is_empty());
+        let invalid = |line: &str| SolverError::InvalidSparseLine(line.to_string());
+
+        let first = lines.next().ok_or(SolverError::EmptyInput)?;
+        let (width, height) = match first.split_whitespace().collect_vec()[..] {
+            ["dimensions", w, h] => match (w.parse::<usize>(), h.parse::<usize>()) {
+                (Ok(w), Ok(h)) if w > 0 && h > 0 => (w, h),
+                _ => return Err(invalid(first)),
+            },
+            _ => return Err(invalid(first)),
+        };
+
+        let mut board = vec![vec![CellTypes::Covered; width]; height];
+        let mut listed = HashSet::new();
+        for line in lines {
+            let mut parts = line.splitn(3, ',').map(str::trim);
+            let (Some(x), Some(y), Some(cell)) = (parts.next(), parts.next(), parts.next()) else { return Err(invalid(line)); };
+            let (Ok(x), Ok(y)) = (x.parse::<usize>(), y.parse::<usize>()) else { return Err(invalid(line)); };
+
+            let cell = match cell.strip_prefix('(').and_then(|range| range.strip_suffix(')')) {
+                Some(range) => CellTypes::from_range(range)?,
+                None => {
+                    let mut chars = cell.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => CellTypes::from_char(c)?,
+                        _ => return Err(invalid(line)),
+                    }
+                }
+            };
+
+            if x >= width || y >= height {
+                return Err(SolverError::CellOutOfBounds { x, y, width, height });
+            }
+            if !listed.insert((x, y)) {
+                return Err(SolverError::DuplicateCell { x, y });
+            }
+            board[y][x] = cell;
+        }
+
+        Ok(Board::new(board, Vec::new(), Adjacency::default()))
+    }
+
+    /// Returns the `w` by `h` sub-board with its top left corner at (`x`, `y`). Mine counts are
+    /// dropped as they no longer describe the cropped board.
+    pub fn crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<Board, SolverError> {
+        self.check_crop(x, y, w, h)?;
+
+        let board = self.board[y..y + h].iter()
+            .map(|line| line[x..x + w].to_vec())
+            .collect();
+
+        Ok(Board::new(board, Vec::new(), self.adjacency))
+    }
+
+    /// Like `crop`, but also keeps the ring of cells around the rectangle that lie on the board.
+    /// The rectangle itself still has to fit in the board.
+    pub fn crop_with_border(&self, x: usize, y: usize, w: usize, h: usize) -> Result<Board, SolverError> {
+        self.check_crop(x, y, w, h)?;
+
+        let (x1, y1) = (x.saturating_sub(1), y.saturating_sub(1));
+        let (x2, y2) = ((x + w + 1).min(self.width), (y + h + 1).min(self.height));
+        self.crop(x1, y1, x2 - x1, y2 - y1)
+    }
+
+    fn check_crop(&self, x: usize, y: usize, w: usize, h: usize) -> Result<(), SolverError> {
+        // Checked without adding to `x` and `y` so huge values can't overflow
+        if w == 0 || h == 0 || w > self.width || h > self.height || x > self.width - w || y > self.height - h {
+            return Err(SolverError::InvalidCrop { x, y, w, h, width: self.width, height: self.height });
+        }
+        Ok(())
+    }
+
+    /// Whether there's nothing to solve from - no numbers, bombs or mine counts, only covered cells
+    pub fn has_no_information(&self) -> bool {
+        self.mine_counts.is_empty()
+            && self.covered_cells().next().is_some()
+            && self.board.iter().flatten().all(|cell| cell.is_covered() || matches!(cell, CellTypes::Absent))
+    }
+
+    /// Relaxes each number with neighbours past the edge of the board into a range, as those
+    /// neighbours may hold any of its bombs - N with K such neighbours becomes at least N - K and
+    /// at most N bombs in view
+    pub fn open_border(&mut self) {
+        let offsets = self.adjacency.offsets().len();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some((min, max)) = self.board[y][x].value_range() else { continue; };
+                let outside = (offsets - self.neighbours(x, y).count()) as u8;
+                self.board[y][x] = match min.saturating_sub(outside) {
+                    min if min == max => CellTypes::Value(max),
+                    min => CellTypes::ValueRange { min, max },
+                };
+            }
+        }
+    }
+
+    /// Every covered cell static analysis can decide, as the safe cells and the bombs, row by row.
+    /// Runs the default rules - satisfied counts, remaining bombs, subset elimination and mine
+    /// count endgames - to a fixpoint, also with `TrialDeduction` if `trial` is set. Never searches,
+    /// so it's quick but can miss cells only the search decides.
+    pub fn all_forced(&self, trial: bool) -> ForcedCells {
+        let mut rules = rules::default_rules();
+        if trial {
+            rules.push(Box::new(rules::TrialDeduction));
+        }
+
+        let mut board = self.clone();
+        board.apply_rules(&rules);
+        self.covered_cells()
+            .filter(|&(x, y)| !board.board[y][x].is_covered())
+            .partition(|&(x, y)| !matches!(board.board[y][x], CellTypes::Bomb))
+    }
+
+    /// Known bombs no number touches, row by row. Nothing on the board depends on them so they
+    /// can't be checked.
+    pub fn unsupported_bombs(&self) -> Vec<(usize, usize)> {
+        (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(self.board[y][x], CellTypes::Bomb))
+            .filter(|&(x, y)| !self.neighbours(x, y).any(|(x, y)| matches!(self.board[y][x].value_range(), Some((_, max)) if max > 0)))
+            .collect()
+    }
+
+    /// Positions of the covered cells, row by row
+    pub fn covered_cells(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.height)
+            .flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.board[y][x].is_covered())
+    }
+
+    /// In-bounds cells surrounding (`x`, `y`) under the board's adjacency
+    pub fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbour_cache[y * self.width + x].iter().copied()
+    }
+
+    /// For a nonzero number, the bombs it still requires and its covered neighbours
+    pub fn constraint(&self, x: usize, y: usize) -> Option<(i32, Vec<(usize, usize)>)> {
+        let mut required = match &self.board[y][x] {
+            CellTypes::Value(v) if *v > 0 => *v as i32,
+            _ => return None,
+        };
+
+        let mut covered = Vec::new();
+        for (x, y) in self.neighbours(x, y) {
+            match &self.board[y][x] {
+                CellTypes::Value(_) | CellTypes::UnknownValue | CellTypes::Absent | CellTypes::ValueRange { .. } => continue,
+                CellTypes::Bomb => required -= 1,
+                CellTypes::Covered | CellTypes::Flagged => covered.push((x, y)),
+            };
+        }
+
+        Some((required, covered))
+    }
+
+    /// Which numbers make the covered cell at (`x`, `y`) `cell` on this board - a bomb, or safe
+    /// for anything else. Numbers that force it alone are preferred over all of the numbers around it.
+    pub fn reason(&self, x: usize, y: usize, cell: CellTypes) -> Reason {
+        let numbers = self.neighbours(x, y).filter_map(|(nx, ny)| self.constraint(nx, ny).map(|constraint| ((nx, ny), constraint))).collect_vec();
+        let bombs = |(x, y): (usize, usize)| self.neighbours(x, y).filter(|&(x, y)| matches!(self.board[y][x], CellTypes::Bomb)).collect_vec();
+        // Neighbours come in adjacency order, so sort every list to match the rest of the output
+        let row_major = |mut cells: Vec<(usize, usize)>| {
+            cells.sort_by_key(|&(x, y)| (y, x));
+            cells
+        };
+        let value = |(x, y): (usize, usize)| match self.board[y][x] {
+            CellTypes::Value(value) => value,
+            _ => 0,
+        };
+
+        let forcing = match cell {
+            CellTypes::Bomb => numbers.iter().find(|(_, (required, covered))| *required > 0 && *required as usize == covered.len()),
+            _ => numbers.iter().find(|(_, (required, _))| *required == 0),
+        };
+        match (forcing, cell) {
+            (Some(&(number, (_, ref covered))), CellTypes::Bomb) => {
+                Reason::Filled { number, value: value(number), mines: row_major(bombs(number).into_iter().chain(covered.iter().copied()).collect()) }
+            }
+            (Some(&(number, _)), _) => Reason::Satisfied { number, value: value(number), mines: row_major(bombs(number)) },
+            (None, _) => {
+                // A number whose covered cells are a subset of another's, as `SubsetElimination` uses
+                let pair = numbers.iter().find_map(|&(larger, (required, ref covered))| {
+                    self.neighbours(larger.0, larger.1).flat_map(|(nx, ny)| self.neighbours(nx, ny)).find(|&smaller| {
+                        let Some((smaller_required, smaller_covered)) = self.constraint(smaller.0, smaller.1) else { return false; };
+                        let difference = covered.iter().filter(|cell| !smaller_covered.contains(cell)).count();
+                        let needed = required - smaller_required;
+                        smaller != larger && !smaller_covered.is_empty() && !smaller_covered.contains(&(x, y))
+                            && smaller_covered.iter().all(|cell| covered.contains(cell))
+                            && if matches!(cell, CellTypes::Bomb) { needed as usize == difference } else { needed == 0 }
+                    }).map(|smaller| vec![smaller, larger])
+                });
+                Reason::Combined(row_major(pair.unwrap_or_else(|| numbers.into_iter().map(|(number, _)| number).collect())))
+            }
+        }
+    }
+
+    /// Runs the deduction rules until none of them can make further progress, returning whether
+    /// anything changed
+    pub fn apply_rules(&mut self, rules: &[Box<dyn DeductionRule>]) -> bool {
+        self.apply_rules_with(rules, |_| {})
+    }
+
+    /// Like `apply_rules` but calls `on_pass` with the board after every pass that changed it
+    pub fn apply_rules_with(&mut self, rules: &[Box<dyn DeductionRule>], mut on_pass: impl FnMut(&Board)) -> bool {
+        let mut change_made = false;
+
+        loop {
+            let mut changed = false;
+            for rule in rules {
+                changed |= rule.apply(self);
+            }
+
+            if !changed {
+                break;
+            }
+            change_made = true;
+            on_pass(self);
+        }
+
+        change_made
+    }
+
+    /// Like `apply_rules` but returns each time a rule changed the board, with the cells it set
+    /// and, if `explain` is set, the reason for each
+    pub fn deduction_steps(&mut self, rules: &[Box<dyn DeductionRule>], explain: bool) -> Vec<Deduction> {
+        let mut steps = Vec::new();
+
+        loop {
+            let mut changed = false;
+            for rule in rules {
+                let before = self.clone();
+                if rule.apply(self) {
+                    let cells = before.diff(self).unwrap().into_iter().map(|(x, y, _, after)| (x, y, after)).collect_vec();
+                    let reasons = if explain { cells.iter().map(|&(x, y, cell)| before.reason(x, y, cell)).collect() } else { Vec::new() };
+                    steps.push((rule.name(), cells, reasons));
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        steps
+    }
+
+    pub fn validate_board(&self) -> Result<usize, SolverError> {
+        let mut to_satisfy = 0;
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                to_satisfy += self.check_number(x, y)?;
+            }
+        }
+
+        for mine_count in &self.mine_counts {
+            let (mut bombs, mut covered, mut flagged) = (0, 0, 0);
+            for y in mine_count.y..mine_count.y + mine_count.h {
+                for x in mine_count.x..mine_count.x + mine_count.w {
+                    match &self.board[y][x] {
+                        CellTypes::Bomb => bombs += 1,
+                        CellTypes::Covered => covered += 1,
+                        CellTypes::Flagged => {
+                            covered += 1;
+                            flagged += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            // The total for the whole board gets its own errors, as it's usually from --mines. Flags
+            // are only ever resolved during the search, so too many of them means they're wrong.
+            if (mine_count.w, mine_count.h) == (self.width, self.height) {
+                if bombs > mine_count.max {
+                    return Err(SolverError::TooManyMinesPlaced { placed: bombs, declared: mine_count.max });
+                }
+                if flagged > mine_count.max {
+                    return Err(SolverError::TooManyFlags { flagged, declared: mine_count.max });
+                }
+            }
+
+            if bombs > mine_count.max {
+                return Err(SolverError::RegionTooManyBombs { region: mine_count.clone(), excess: bombs - mine_count.max });
+            }
+
+            let required = mine_count.min.saturating_sub(bombs);
+            if covered < required {
+                return Err(SolverError::RegionNotEnoughCells { region: mine_count.clone(), required, available: covered });
+            }
+
+            to_satisfy += required;
+        }
+
+        Ok(to_satisfy)
+    }
+
+    /// Checks the number at (`x`, `y`) against its neighbours alone, returning how many more bombs
+    /// it needs. Cells that aren't numbers, and zeros, always pass.
+    fn check_number(&self, x: usize, y: usize) -> Result<usize, SolverError> {
+        let Some((min, max)) = self.board[y][x].value_range() else { return Ok(0); };
+        if max == 0 { return Ok(0); }
+        self.check_value(x, y)?;
+
+        let (mut bombs, mut possible_cells) = (0, 0u8);
+        for (x, y) in self.neighbours(x, y) {
+            match &self.board[y][x] {
+                CellTypes::Bomb => bombs += 1,
+                CellTypes::Covered | CellTypes::Flagged => possible_cells += 1,
+                _ => continue,
+            };
+        }
+
+        if bombs > max as i32 {
+            return Err(SolverError::TooManyBombs { x, y, excess: (bombs - max as i32) as usize });
+        }
+        let required = (min as i32 - bombs).max(0);
+
+        if possible_cells == 0 && required > 0 {
+            return Err(SolverError::NeighboursDetermined { x, y, required: required as usize });
+        }
+
+        if (possible_cells as i32) < required {
+            return Err(SolverError::NotEnoughCells { x, y, required: required as usize, available: possible_cells as usize });
+        }
+
+        Ok(required as usize)
+    }
+
+    /// Fewest numbers that, read as unknown values instead, leave the board with a solution - the
+    /// likely misread cells of an invalid board, row by row. Every number that's wrong by itself is
+    /// included, along with the fewest others, up to `SUSPECT_LIMIT`, the search needs changed too.
+    /// None if no such set exists, such as when a mine count has too many bombs.
+    ///
+    /// Each extra number tried runs a full search, so this is much slower than solving.
+    pub fn suspect_numbers(&self) -> Option<Vec<(usize, usize)>> {
+        let (invalid, others): (Vec<_>, Vec<_>) = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(self.board[y][x].value_range(), Some((_, max)) if max > 0))
+            .partition(|&(x, y)| self.check_number(x, y).is_err());
+
+        let mut board = self.clone();
+        for &(x, y) in &invalid {
+            board.board[y][x] = CellTypes::UnknownValue;
+        }
+
+        for extra in 0..=SUSPECT_LIMIT.min(others.len()) {
+            for changed in others.iter().copied().combinations(extra) {
+                let mut trial = board.clone();
+                for &(x, y) in &changed {
+                    trial.board[y][x] = CellTypes::UnknownValue;
+                }
+
+                if trial.mine_configurations(None).next().is_some() {
+                    let mut suspects = invalid.iter().copied().chain(changed).collect_vec();
+                    suspects.sort_by_key(|&(x, y)| (y, x));
+                    return Some(suspects);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The largest number the cell at (`x`, `y`) can have - its count of neighbours that are part
+    /// of the board, regardless of what they currently are
+    pub fn value_limit(&self, x: usize, y: usize) -> usize {
+        self.neighbours(x, y).filter(|&(x, y)| !matches!(self.board[y][x], CellTypes::Absent)).count()
+    }
+
+    /// Checks that the cell isn't a number larger than `value_limit`, or a range whose smallest
+    /// number is. Parsing and `validate_board` both use this so every adjacency agrees on it.
+    pub fn check_value(&self, x: usize, y: usize) -> Result<(), SolverError> {
+        let Some((value, _)) = self.board[y][x].value_range() else { return Ok(()); };
+        let neighbours = self.value_limit(x, y);
+        if value as usize > neighbours {
+            return Err(SolverError::ValueExceedsNeighbours { x, y, value, neighbours });
+        }
+        Ok(())
+    }
+
+    /// Lowers every number larger than `value_limit` to it, and the ends of ranges above it,
+    /// returning the position and original cell of each change
+    pub fn clamp_values(&mut self) -> Vec<(usize, usize, CellTypes)> {
+        let mut clamped = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some((min, max)) = self.board[y][x].value_range() else { continue; };
+                let limit = self.value_limit(x, y) as u8;
+                if max <= limit {
+                    continue;
+                }
+
+                clamped.push((x, y, self.board[y][x]));
+                self.board[y][x] = match min.min(limit) {
+                    min if min == limit => CellTypes::Value(limit),
+                    min => CellTypes::ValueRange { min, max: limit },
+                };
+            }
+        }
+        clamped
+    }
+
+    /// Whether a mine count has room for more bombs than it needs. A solved board with slack may
+    /// still be solved with more bombs, so the search keeps going from it.
+    pub fn has_slack(&self) -> bool {
+        let range_slack = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y))).any(|(x, y)| {
+            let CellTypes::ValueRange { max, .. } = self.board[y][x] else { return false; };
+            let bombs = self.neighbours(x, y).filter(|&(x, y)| matches!(self.board[y][x], CellTypes::Bomb)).count();
+            bombs < max as usize && self.neighbours(x, y).any(|(x, y)| self.board[y][x].is_covered())
+        });
+
+        range_slack || self.mine_counts.iter().any(|mine_count| {
+            let bombs = (mine_count.y..mine_count.y + mine_count.h)
+                .flat_map(|y| (mine_count.x..mine_count.x + mine_count.w).map(move |x| (x, y)))
+                .filter(|&(x, y)| matches!(self.board[y][x], CellTypes::Bomb))
+                .count();
+            bombs < mine_count.max
+        })
+    }
+
+    /// Whether every number is exactly satisfied by the placed bombs and every mine count has at
+    /// least its fewest. Covered cells may remain on a solved board - nothing requires them to be
+    /// bombs, so they're all safe unless `has_slack` allows more.
+    pub fn is_solved(&self) -> bool {
+        matches!(self.validate_board(), Ok(0))
+    }
+
+    /// Covered cells that touch no number above 0 and lie in no mine count. A bomb in one changes
+    /// nothing `validate_board` checks, so the search never places one there.
+    ///
+    /// This only depends on where the numbers and mine counts are, which never changes during the
+    /// search, so skipping these cells is safe from every branch. Every other covered cell touches a
+    /// number or lies in a mine count, so whether it's a bomb matters to `validate_board`.
+    pub fn unconstrained_cells(&self) -> HashSet<(usize, usize)> {
+        self.covered_cells()
+            .filter(|&(x, y)| !self.neighbours(x, y).any(|(x, y)| matches!(self.board[y][x].value_range(), Some((_, max)) if max > 0)))
+            .filter(|&(x, y)| !self.mine_counts.iter().any(|m| (m.x..m.x + m.w).contains(&x) && (m.y..m.y + m.h).contains(&y)))
+            .collect()
+    }
+
+    /// Solved, Open. `ignore` should be `unconstrained_cells` of the board the search started from.
+    /// Given that board's `symmetries`, boards are only visited once for all their images under
+    /// them, and the solved boards found need expanding with `orbit`.
+    pub fn get_possible_boards(&self, visited: &mut HashSet<u64>, ignore: &HashSet<(usize, usize)>, symmetries: &[Symmetry]) -> (Vec<Board>, Vec<Board>) {
+        let mut solved_boards = Vec::new();
+        let mut open_boards = Vec::new();
+
+        for (x, y) in self.covered_cells() {
+            if ignore.contains(&(x, y)) {
+                continue
+            }
+
+            let mut new_board = self.clone();
+            new_board.board[y][x] = CellTypes::Bomb;
+
+            let hash = if symmetries.is_empty() { new_board.get_hash() } else { new_board.canonical_hash(symmetries) };
+            if visited.contains(&hash) {
+                continue;
+            }
+            visited.insert(hash);
+
+            match new_board.validate_board() {
+                Ok(0) if new_board.has_slack() => {
+                    open_boards.push(new_board.clone());
+                    solved_boards.push(new_board);
+                }
+                Ok(0) => solved_boards.push(new_board),
+                Ok(_) => open_boards.push(new_board),
+                Err(_) => {}
+            }
+        }
+
+        (solved_boards, open_boards)
+    }
+
+    /// Runs static analysis with `rules` and then the search, compiling the guaranteed cells from
+    /// the possibilities it finds, which are also returned
+    pub fn solve(&self, rules: &[Box<dyn DeductionRule>]) -> (SolveResult, Vec<Board>) {
+        let mut board = self.clone();
+        board.apply_rules(rules);
+
+        let possibilities = board.mine_configurations(None).map(|bombs| board.with_mines(&bombs).unwrap()).collect_vec();
+        (Board::compile_guaranteed(self, &possibilities, &board.unconstrained_cells()), possibilities)
+    }
+
+    /// Every covered cell a number or mine count touches with its chance of being a bomb, safest
+    /// first, with `mines` bombs in total if given. Uses the default rules for static analysis.
+    pub fn move_probabilities(&self, mines: Option<usize>) -> Vec<(usize, usize, f64)> {
+        let mut board = self.clone();
+        if let Some(mines) = mines {
+            board.mine_counts.push(MineCount { x: 0, y: 0, w: self.width, h: self.height, min: mines, max: mines });
+        }
+
+        let (result, _) = board.solve(&rules::default_rules());
+        result.covered_cells(&board).into_iter()
+            .filter_map(|(x, y, _, probability)| probability.map(|probability| (x, y, probability)))
+            .sorted_by(|a, b| a.2.partial_cmp(&b.2).unwrap())
+            .collect()
+    }
+
+    /// Classifies the board by whether static analysis, the search or only a guess decides its
+    /// covered cells. Cells no number touches can't be decided, so any of them make it a guess.
+    pub fn difficulty(&self, rules: &[Box<dyn DeductionRule>]) -> Difficulty {
+        let mut board = self.clone();
+        board.apply_rules(rules);
+        if board.covered_cells().next().is_none() {
+            return Difficulty { level: DifficultyLevel::Trivial, forced: self.covered_cells().count() };
+        }
+
+        let cells = self.solve(rules).0.covered_cells(self);
+        let forced = cells.iter().filter(|cell| cell.2 != CellStatus::Ambiguous).count();
+        let level = if forced == cells.len() { DifficultyLevel::Logic } else { DifficultyLevel::Guess };
+        Difficulty { level, forced }
+    }
+
+    /// Every full set of bomb positions - including the bombs already placed - consistent with the
+    /// numbers, the board's mine counts and `mines` bombs in total if given, in row major order.
+    /// Covered cells no constraint cares about are left without bombs, as in the search.
+    ///
+    /// This is the same search `get_possible_boards` does a step of, so it takes time and memory
+    /// exponential in the number of covered cells next to numbers. On large boards, enumerate each
+    /// of `regions` separately (cropped, or with the rest of the board filled in) and combine them.
+    pub fn mine_configurations(&self, mines: Option<usize>) -> impl Iterator<Item = Vec<(usize, usize)>> {
+        let mut start = self.clone();
+        if let Some(mines) = mines {
+            start.mine_counts.push(MineCount { x: 0, y: 0, w: self.width, h: self.height, min: mines, max: mines });
+        }
+
+        let mut configurations = MineConfigurations {
+            open: VecDeque::new(),
+            solved: Vec::new(),
+            visited: HashSet::new(),
+            ignore: start.unconstrained_cells(),
+        };
+        match start.validate_board() {
+            Ok(0) if start.has_slack() => {
+                configurations.solved.push(start.clone());
+                configurations.open.push_back(start);
+            }
+            Ok(0) => configurations.solved.push(start),
+            Ok(_) => configurations.open.push_back(start),
+            Err(_) => {}
+        }
+        configurations
+    }
+
+    /// Fewest and most bombs a completion of this board can have in total, given `possibilities`
+    /// found by searching it. Any of the unconstrained cells the search skips could also be a bomb,
+    /// so they only count towards the maximum.
+    pub fn bomb_count_bounds(&self, possibilities: &[Board]) -> Option<(usize, usize)> {
+        let counts = possibilities.iter().map(|board| board.board.iter().flatten().filter(|cell| matches!(cell, CellTypes::Bomb)).count());
+        let (min, max) = counts.minmax().into_option()?;
+        Some((min, max + self.unconstrained_cells().len()))
+    }
+
+    /// Rough size of the search as the count of covered cells it has to decide and the size of the
+    /// largest region among them. Each cell can double the boards searched, so a search over `n`
+    /// cells visits up to around 2^n boards, though numbers usually prune far more than that.
+    pub fn estimated_search_size(&self) -> SearchSize {
+        SearchSize {
+            cells: self.covered_cells().count() - self.unconstrained_cells().len(),
+            largest_region: self.regions().iter().map(Vec::len).max().unwrap_or(0),
+        }
+    }
+
+    /// How many of `possibilities` have each total of bombs, fewest first. Like `bomb_count_bounds`
+    /// this doesn't count the unconstrained cells the search skips.
+    pub fn bomb_count_histogram(possibilities: &[Board]) -> Vec<(usize, usize)> {
+        possibilities.iter()
+            .map(|board| board.board.iter().flatten().filter(|cell| matches!(cell, CellTypes::Bomb)).count())
+            .counts()
+            .into_iter()
+            .sorted()
+            .collect()
+    }
+
+    /// The constraints as a Graphviz graph - an edge joins each number to every covered cell it
+    /// still constrains, and the nodes of each region share a colour
+    pub fn constraint_dot(&self) -> String {
+        const COLOURS: [&str; 8] = ["lightblue", "lightgreen", "pink", "gold", "plum", "orange", "aquamarine", "tan"];
+        let region_of = self.regions().into_iter().enumerate()
+            .flat_map(|(i, region)| region.into_iter().map(move |cell| (cell, COLOURS[i % COLOURS.len()])))
+            .collect::<HashMap<_, _>>();
+
+        let mut output = "graph constraints {\n\tnode [style=filled];\n".to_string();
+        let mut covered = HashSet::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some((required, mut cells)) = self.constraint(x, y) else { continue; };
+                cells.sort_by_key(|&(x, y)| (y, x));
+                let Some(colour) = cells.first().and_then(|cell| region_of.get(cell)) else { continue; };
+
+                output.push_str(&format!("\tn{x}_{y} [label=\"{} at [{x}, {y}]\\n{required} left\", shape=box, fillcolor={colour}];\n", self.board[y][x].text()));
+                for (cx, cy) in cells {
+                    if covered.insert((cx, cy)) {
+                        output.push_str(&format!("\tc{cx}_{cy} [label=\"[{cx}, {cy}]\", fillcolor={}];\n", region_of[&(cx, cy)]));
+                    }
+                    output.push_str(&format!("\tn{x}_{y} -- c{cx}_{cy};\n"));
+                }
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Groups the covered cells next to numbers into regions, where two cells share a region if
+    /// they're linked by a chain of numbers that touch both. Regions don't constrain each other.
+    pub fn regions(&self) -> Vec<Vec<(usize, usize)>> {
+        let is_number = |x: usize, y: usize| matches!(self.board[y][x].value_range(), Some((_, max)) if max > 0);
+        let is_covered = |x: usize, y: usize| self.board[y][x].is_covered();
+
+        let mut regions = Vec::new();
+        let mut seen = HashSet::new();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !is_covered(x, y) || !self.neighbours(x, y).any(|(x, y)| is_number(x, y)) || seen.contains(&(x, y)) {
+                    continue;
+                }
+
+                let mut region = Vec::new();
+                let mut to_visit = VecDeque::from([(x, y)]);
+                seen.insert((x, y));
+
+                while let Some((x, y)) = to_visit.pop_front() {
+                    region.push((x, y));
+                    for (nx, ny) in self.neighbours(x, y).filter(|&(x, y)| is_number(x, y)) {
+                        for cell in self.neighbours(nx, ny).filter(|&(x, y)| is_covered(x, y)) {
+                            if seen.insert(cell) {
+                                to_visit.push_back(cell);
+                            }
+                        }
+                    }
+                }
+
+                region.sort_by_key(|&(x, y)| (y, x));
+                regions.push(region);
+            }
+        }
+
+        regions
+    }
+
+    /// Cells that differ between the two boards as (x, y, self, other), row by row
+    pub fn diff(&self, other: &Board) -> Result<Vec<(usize, usize, CellTypes, CellTypes)>, SolverError> {
+        if self.width != other.width || self.height != other.height {
+            return Err(SolverError::DimensionMismatch {
+                first: (self.width, self.height),
+                second: (other.width, other.height),
+            });
+        }
+
+        let mut changes = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.board[y][x].id() != other.board[y][x].id() {
+                    changes.push((x, y, self.board[y][x], other.board[y][x]));
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Combines two observations of the same board, taking the most specific cell from either - a
+    /// number or bomb beats a range of values, which beats an unknown value, which beats covered.
+    /// Ranges are narrowed to the values both allow. Mine counts from both are kept.
+    pub fn merge(&self, other: &Board) -> Result<Board, SolverError> {
+        if self.width != other.width || self.height != other.height {
+            return Err(SolverError::DimensionMismatch {
+                first: (self.width, self.height),
+                second: (other.width, other.height),
+            });
+        }
+
+        let mut merged = self.clone();
+        merged.mine_counts.extend(other.mine_counts.iter().cloned());
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                merged.board[y][x] = match (self.board[y][x], other.board[y][x]) {
+                    (CellTypes::Covered, cell) | (cell, CellTypes::Covered) | (CellTypes::Flagged, cell) | (cell, CellTypes::Flagged) => cell,
+                    (CellTypes::UnknownValue, cell @ (CellTypes::Value(_) | CellTypes::ValueRange { .. }))
+                    | (cell @ (CellTypes::Value(_) | CellTypes::ValueRange { .. }), CellTypes::UnknownValue) => cell,
+                    (CellTypes::ValueRange { min, max }, cell @ CellTypes::Value(value))
+                    | (cell @ CellTypes::Value(value), CellTypes::ValueRange { min, max }) if (min..=max).contains(&value) => cell,
+                    (CellTypes::ValueRange { min: first_min, max: first_max }, CellTypes::ValueRange { min: second_min, max: second_max })
+                        if first_min.max(second_min) <= first_max.min(second_max) => {
+                        match (first_min.max(second_min), first_max.min(second_max)) {
+                            (min, max) if min == max => CellTypes::Value(min),
+                            (min, max) => CellTypes::ValueRange { min, max },
+                        }
+                    }
+                    (first @ CellTypes::ValueRange { .. }, second) | (first, second @ CellTypes::ValueRange { .. }) =>
+                        return Err(SolverError::MergeConflict { x, y, first, second }),
+                    (first, second) if first.id() == second.id() => first,
+                    (first, second) => return Err(SolverError::MergeConflict { x, y, first, second }),
+                };
+            }
+        }
+
+        Ok(merged)
+    }
+
+    /// Packs which cells are bombs into 64 bit words, row by row. Cell `y * width + x` is bit
+    /// `i % 64` of word `i / 64`, counting from the least significant bit.
+    pub fn bomb_mask(&self) -> Vec<u64> {
+        let mut mask = vec![0u64; (self.width * self.height).div_ceil(64)];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if matches!(self.board[y][x], CellTypes::Bomb) {
+                    let i = y * self.width + x;
+                    mask[i / 64] |= 1 << (i % 64);
+                }
+            }
+        }
+        mask
+    }
+
+    /// Places the bombs from a mask made by `bomb_mask` onto this board. Bombs can only go on
+    /// covered cells or cells that are already bombs.
+    pub fn with_bomb_mask(&self, mask: &[u64]) -> Result<Board, SolverError> {
+        let expected = (self.width * self.height).div_ceil(64);
+        if mask.len() != expected {
+            return Err(SolverError::MaskLength { expected, found: mask.len() });
+        }
+
+        let mut board = self.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = y * self.width + x;
+                if mask[i / 64] & (1 << (i % 64)) == 0 {
+                    continue;
+                }
+
+                match board.board[y][x] {
+                    CellTypes::Covered | CellTypes::Flagged | CellTypes::Bomb => board.board[y][x] = CellTypes::Bomb,
+                    _ => return Err(SolverError::MaskOnRevealedCell { x, y }),
+                }
+            }
+        }
+
+        Ok(board)
+    }
+
+    /// The board after placing a bomb at (`x`, `y`) and running static analysis, or why that
+    /// contradicts the numbers
+    pub fn assume_bomb(&self, x: usize, y: usize, rules: &[Box<dyn DeductionRule>]) -> Result<Board, SolverError> {
+        self.assume(x, y, CellTypes::Bomb, rules)
+    }
+
+    /// As `assume_bomb`, for revealing (`x`, `y`) as safe
+    pub fn assume_safe(&self, x: usize, y: usize, rules: &[Box<dyn DeductionRule>]) -> Result<Board, SolverError> {
+        self.assume(x, y, CellTypes::Value(0), rules)
+    }
+
+    fn assume(&self, x: usize, y: usize, cell: CellTypes, rules: &[Box<dyn DeductionRule>]) -> Result<Board, SolverError> {
+        if x >= self.width || y >= self.height {
+            return Err(SolverError::CellOutOfBounds { x, y, width: self.width, height: self.height });
+        }
+        if !self.board[y][x].is_covered() {
+            return Err(SolverError::CellNotCovered { x, y });
+        }
+
+        let mut board = self.clone();
+        board.board[y][x] = cell;
+        board.validate_board()?;
+        board.apply_rules(rules);
+        board.validate_board()?;
+        Ok(board)
+    }
+
+    /// Places bombs on the given cells, which have to be covered or bombs already
+    pub fn with_mines(&self, cells: &[(usize, usize)]) -> Result<Board, SolverError> {
+        let mut board = self.clone();
+        for &(x, y) in cells {
+            if x >= self.width || y >= self.height {
+                return Err(SolverError::CellOutOfBounds { x, y, width: self.width, height: self.height });
+            }
+
+            match board.board[y][x] {
+                CellTypes::Covered | CellTypes::Flagged | CellTypes::Bomb => board.board[y][x] = CellTypes::Bomb,
+                _ => return Err(SolverError::MineOnRevealedCell { x, y }),
+            }
         }
+
+        Ok(board)
     }
 
-    pub fn char(&self) -> char {
-        /*match &self {
-            CellTypes::Covered => '◼',
-            CellTypes::Bomb => 'x',
-            CellTypes::Value(v) =>  {
-                if *v == 0 {
-                    '◻'
-                }
-                else {
-                    v.to_string().chars().next().unwrap()
-                }
+    /// Checks that placing the bombs of `solution` and revealing every other covered cell gives
+    /// each number and mine count exactly its bombs
+    pub fn verify_solution(&self, solution: &Board) -> Result<(), SolverError> {
+        if (self.width, self.height) != (solution.width, solution.height) {
+            return Err(SolverError::DimensionMismatch { first: (self.width, self.height), second: (solution.width, solution.height) });
+        }
+
+        let bombs = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| matches!(solution.board[y][x], CellTypes::Bomb))
+            .collect_vec();
+        let mut board = self.with_mines(&bombs)?;
+        for (x, y) in self.covered_cells() {
+            if !matches!(board.board[y][x], CellTypes::Bomb) {
+                board.board[y][x] = CellTypes::Value(0);
             }
-        }*/
-        match &self {
-            CellTypes::Covered => '?',
-            CellTypes::Bomb => 'x',
-            CellTypes::Value(v) =>  {
-                if *v == 0 {
-                    '-'
+        }
+
+        // validate_board skips zeros, so each number is counted here to hold it to exactly its bombs
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let Some((min, max)) = self.board[y][x].value_range() else { continue; };
+                let bombs = board.neighbours(x, y).filter(|&(x, y)| matches!(board.board[y][x], CellTypes::Bomb)).count();
+                if bombs > max as usize {
+                    return Err(SolverError::TooManyBombs { x, y, excess: bombs - max as usize });
                 }
-                else {
-                    v.to_string().chars().next().unwrap()
+                if bombs < min as usize {
+                    return Err(SolverError::NeighboursDetermined { x, y, required: min as usize - bombs });
                 }
             }
         }
+
+        board.validate_board().map(|_| ())
     }
 
-    pub fn id(&self) -> u8 {
-        match &self {
-            CellTypes::Value(v) => *v,
-            CellTypes::Covered => 9,
-            CellTypes::Bomb => 10
+    /// One line per row of space separated `CellTypes::id` values, or with `one_hot` of `CELL_IDS`
+    /// zeros and ones per cell, the one at its id
+    pub fn matrix(&self, one_hot: bool) -> String {
+        let mut output = String::new();
+        for line in &self.board {
+            let values = line.iter().flat_map(|cell| if one_hot {
+                (0..CELL_IDS as u8).map(|id| u8::from(id == cell.id())).collect_vec()
+            } else {
+                vec![cell.id()]
+            });
+            output.push_str(&values.map(|value| value.to_string()).join(" "));
+            output.push('\n');
         }
+        output
     }
-}
 
-#[derive(Debug, Clone)]
-struct Board {
-    board: Vec<Vec<CellTypes>>,
-    width: usize,
-    height: usize,
-}
+    /// Board string and dimensions for the JSON output
+    pub fn json_summary(&self) -> JsonObject {
+        JsonObject::new()
+            .field("board", &self.to_string())
+            .field("width", &self.width)
+            .field("height", &self.height)
+    }
 
-impl Board {
-    pub fn compile_guaranteed(base: &Board, possibilities: &[Board], ignore: &HashSet<(usize, usize)>) -> String {
-        let mut board = Vec::with_capacity(base.height);
-        for _ in 0..base.height {
-            let mut line = Vec::with_capacity(base.width);
-            for _ in 0..base.width {
-                // Bomb, Not
-                line.push((0usize, 0usize));
+    /// The board reflected or rotated by `symmetry`, along with its mine counts
+    pub fn transformed(&self, symmetry: Symmetry) -> Board {
+        let (width, height) = if symmetry.transpose { (self.height, self.width) } else { (self.width, self.height) };
+        let mut board = vec![vec![CellTypes::Covered; width]; height];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (tx, ty) = symmetry.apply(x, y, self.width, self.height);
+                board[ty][tx] = self.board[y][x];
             }
-            board.push(line);
         }
 
-        for possibility in possibilities {
-            for x in 0..base.width {
-                for y in 0..base.height {
-                    let is_bomb = match &possibility.board[y][x] {
-                        CellTypes::Value(_) => continue,
-                        CellTypes::Bomb => true,
-                        CellTypes::Covered => false,
-                    };
+        let mine_counts = self.mine_counts.iter().map(|mine_count| {
+            let (x1, y1) = symmetry.apply(mine_count.x, mine_count.y, self.width, self.height);
+            let (x2, y2) = symmetry.apply(mine_count.x + mine_count.w - 1, mine_count.y + mine_count.h - 1, self.width, self.height);
+            let (x, y) = (x1.min(x2), y1.min(y2));
+            MineCount { x, y, w: x1.max(x2) - x + 1, h: y1.max(y2) - y + 1, ..mine_count.clone() }
+        }).collect();
 
-                    if is_bomb {
-                        board[y][x] = (board[y][x].0 + 1, board[y][x].1);
-                    }
-                    else {
-                        board[y][x] = (board[y][x].0, board[y][x].1 + 1);
-                    }
+        // Same sized boards have the same neighbours, so only a transpose of a rectangle needs new ones
+        let neighbour_cache = if (width, height) == (self.width, self.height) {
+            self.neighbour_cache.clone()
+        } else {
+            Rc::new(Board::neighbour_lists(width, height, self.adjacency))
+        };
+        Board { board, width, height, mine_counts, adjacency: self.adjacency, neighbour_cache }
+    }
+
+    /// Reflections and rotations that leave the board, including its mine counts, unchanged. The
+    /// identity is always one of them.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::all(self.width, self.height).into_iter().filter(|&symmetry| {
+            let transformed = self.transformed(symmetry);
+            transformed.get_hash() == self.get_hash()
+                && transformed.mine_counts.iter().all(|mine_count| self.mine_counts.contains(mine_count))
+        }).collect()
+    }
+
+    /// Hash shared by every image of the board under `symmetries`
+    pub fn canonical_hash(&self, symmetries: &[Symmetry]) -> u64 {
+        let mut ids = vec![0; self.width * self.height];
+        symmetries.iter().map(|&symmetry| {
+            for y in 0..self.height {
+                for x in 0..self.width {
+                    let (tx, ty) = symmetry.apply(x, y, self.width, self.height);
+                    // Symmetries only change the shape of square boards, so the row length is unchanged
+                    ids[ty * self.width + tx] = self.board[y][x].id();
                 }
             }
+            let mut hasher = DefaultHasher::new();
+            ids.hash(&mut hasher);
+            hasher.finish()
+        }).min().unwrap()
+    }
+
+    /// The distinct images of the board under `symmetries`, or just the board without any
+    pub fn orbit(&self, symmetries: &[Symmetry]) -> Vec<Board> {
+        if symmetries.is_empty() {
+            return vec![self.clone()];
         }
 
-        let mut board_probabilities = Vec::with_capacity(base.height);
-        let mut output = Vec::with_capacity(base.height);
-        let mut found = false;
+        let mut seen = HashSet::new();
+        symmetries.iter()
+            .map(|&symmetry| self.transformed(symmetry))
+            .filter(|board| seen.insert(board.get_hash()))
+            .collect()
+    }
 
-        for y in 0..base.height {
-            output.push(Vec::with_capacity(base.width));
-            board_probabilities.push(Vec::with_capacity(base.width));
-            for x in 0..base.width {
-                board_probabilities[y].push(0f64);
-                if board[y][x].0 == 0 && board[y][x].1 == 0 || ignore.contains(&(x, y)) || (board[y][x].0 > 0 && board[y][x].1 == 0 && matches!(base.board[y][x], CellTypes::Bomb)) {
-                    output[y].push(base.board[y][x].char());
-                }
-                else if board[y][x].0 > 0 && board[y][x].1 == 0 {
-                    output[y].push('#');
-                    found = true;
-                }
-                else if board[y][x].0 == 0 && board[y][x].1 > 0 {
-                    output[y].push('O');
-                    found = true;
-                }
-                else {
-                    if !found {
-                        board_probabilities[y][x] = (board[y][x].1 as f64) / (board[y][x].1 as f64 + board[y][x].0 as f64);
-                    }
+    pub fn get_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
 
-                    output[y].push('?');
-                }
+        for line in &self.board {
+            for cell in line {
+                cell.id().hash(&mut hasher);
             }
         }
 
+        hasher.finish()
+    }
+}
 
-        if found {
-            let output_string = output.into_iter().map(|line| line.into_iter().collect::<String>()).join("\n");
-            format!("Guaranteed cells:\n{output_string}\n\nKey:\n\t'#' - Guaranteed bomb\n\t'O' - Guaranteed safe\n")
-        }
-        else {
-            let mut max: Option<(f64, (usize, usize))> = None;
-            for y in 0..base.height {
-                for x in 0..base.width {
-                    if max.is_none() || board_probabilities[y][x] > max.unwrap().0 {
-                        max = Some((board_probabilities[y][x], (x, y)));
-                    }
-                }
+impl FromStr for Board {
+    type Err = SolverError;
+
+    fn from_str(s: &str) -> Result<Board, SolverError> {
+        Board::from_string(s.to_string())
+    }
+}
+
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut output = String::new();
+
+        for line in &self.board {
+            for cell in line {
+                output.push_str(&cell.text());
             }
+            output.push('\n');
+        }
+
+        // Strip the trailing newline - a board with no rows has none
+        output.pop();
+
+        f.write_str(&output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn parse(input: &str) -> Result<Board, SolverError> {
+        Board::from_string(input.to_string())
+    }
+
+    fn parse_with(input: &str, adjacency: Adjacency) -> Result<Board, SolverError> {
+        Board::from_string_with(input.to_string(), &ParseOptions { adjacency, ..ParseOptions::default() }).map(|(board, _)| board)
+    }
+
+    /// Each adjacency with the neighbours of a corner, an edge and an interior cell
+    const LIMITS: [(Adjacency, usize, usize, usize); 3] = [
+        (Adjacency::King, 3, 5, 8),
+        (Adjacency::Orthogonal, 2, 3, 4),
+        (Adjacency::Diagonal, 1, 2, 4),
+    ];
 
-            let (x, y) = max.unwrap().1;
-            let probability = max.unwrap().0;
+    #[test]
+    fn blank_lines_in_the_middle_are_skipped() {
+        let glued = parse("1?\n\n??\n").unwrap();
+        let plain = parse("1?\n??").unwrap();
+        assert_eq!((glued.width, glued.height), (2, 2));
+        assert_eq!(glued.to_string(), plain.to_string());
+    }
+
+    #[test]
+    fn lines_of_spaces_are_not_blank() {
+        assert!(matches!(parse("1?\n  \n??"), Err(SolverError::UnrecognisedCharacter(' '))));
+    }
+
+    #[test]
+    fn corner_number_above_its_neighbours_is_rejected() {
+        assert!(matches!(parse("4?\n??"), Err(SolverError::ValueExceedsNeighbours { x: 0, y: 0, value: 4, neighbours: 3 })));
+        assert!(parse("3?\n??").is_ok());
+    }
 
-            output[y][x] = '@';
-            let output_string = output.into_iter().map(|line| line.into_iter().collect::<String>()).join("\n");
+    #[test]
+    fn edge_number_above_its_neighbours_is_rejected() {
+        assert!(matches!(parse("?6?\n???"), Err(SolverError::ValueExceedsNeighbours { x: 1, y: 0, value: 6, neighbours: 5 })));
+        assert!(parse("?5?\n???").is_ok());
+    }
 
-            format!("{output_string}\n\nCell marked '@' is most likely to be empty with a chance of {:.2}% - No cells are definitively a bomb or safe\n", (probability * 100.0))
+    #[test]
+    fn value_limit_follows_adjacency() {
+        for (adjacency, corner, edge, interior) in LIMITS {
+            let board = parse_with("???\n???\n???", adjacency).unwrap();
+            assert_eq!(board.value_limit(0, 0), corner, "{adjacency:?} corner");
+            assert_eq!(board.value_limit(1, 0), edge, "{adjacency:?} edge");
+            assert_eq!(board.value_limit(1, 1), interior, "{adjacency:?} interior");
         }
     }
 
-    pub fn from_string(input: String) -> Result<Board, String> {
-        let mut board = Vec::new();
-        let mut width = None;
+    #[test]
+    fn parsing_rejects_numbers_above_the_limit_for_each_adjacency() {
+        for (adjacency, corner, _, _) in LIMITS {
+            let allowed = parse_with(&format!("{corner}??\n???\n???"), adjacency);
+            assert!(allowed.is_ok(), "{adjacency:?} rejected {corner} in a corner");
 
-        let lines = input.lines();
+            let over = parse_with(&format!("{}??\n???\n???", corner + 1), adjacency);
+            assert!(
+                matches!(over, Err(SolverError::ValueExceedsNeighbours { x: 0, y: 0, neighbours, .. }) if neighbours == corner),
+                "{adjacency:?} accepted {} in a corner", corner + 1
+            );
+        }
+    }
 
-        for line_str in lines {
-            if line_str.len() == 0 {
-                continue;
-            }
-            if width.is_none() {
-                width = Some(line_str.len());
-            } else if width.unwrap() != line_str.len() {
-                return Err(format!(
-                    "Irregular line width - expected {} found {}",
-                    width.unwrap(),
-                    line_str.len()
-                ));
+    #[test]
+    fn validation_agrees_with_parsing_after_changing_adjacency() {
+        for (adjacency, corner, _, _) in LIMITS {
+            let mut board = parse("3??\n???\n???").unwrap();
+            board.set_adjacency(adjacency);
+            let result = board.validate_board();
+            if corner >= 3 {
+                assert!(result.is_ok(), "{adjacency:?}");
+            } else {
+                assert!(matches!(result, Err(SolverError::ValueExceedsNeighbours { neighbours, .. }) if neighbours == corner), "{adjacency:?}");
             }
+        }
+    }
 
-            let mut line = Vec::new();
-            for c in line_str.chars() {
-                line.push(CellTypes::from_char(c)?);
-            }
-            board.push(line);
+    /// Bombs of every full layout of `board`, found by trying every subset of its covered cells with
+    /// no pruning at all. Bombs in `unconstrained_cells` are left out, as the search never places them.
+    fn brute_force(board: &Board) -> BTreeSet<Vec<(usize, usize)>> {
+        let ignore = board.unconstrained_cells();
+        let cells = board.covered_cells().collect_vec();
+        (0..1u32 << cells.len())
+            .filter_map(|mask| {
+                let bombs = cells.iter().enumerate().filter(|(i, _)| mask >> i & 1 == 1).map(|(_, &cell)| cell).collect_vec();
+                let solution = board.with_mines(&bombs).unwrap();
+                board.verify_solution(&solution).ok()?;
+                Some((0..board.height).flat_map(|y| (0..board.width).map(move |x| (x, y)))
+                    .filter(|&(x, y)| matches!(solution.board[y][x], CellTypes::Bomb) && !ignore.contains(&(x, y)))
+                    .collect_vec())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn skipping_unconstrained_cells_keeps_every_layout() {
+        // A bomb next to only the range changes nothing it needs, so the old ignore rule skipped
+        // that cell in every branch. The top right cell touches no number at all.
+        let mut boards = vec![
+            parse("(0-1)??\n???\n??1").unwrap(),
+            parse("1?1\n???\n???").unwrap(),
+            parse("???\n?2?\n???\n?1?").unwrap(),
+        ];
+        let mut counted = parse("??1?\n????\n????").unwrap();
+        counted.mine_counts.push(MineCount { x: 0, y: 0, w: 4, h: 3, min: 2, max: 2 });
+        boards.push(counted);
+
+        for board in boards {
+            let searched = board.mine_configurations(None).collect_vec();
+            let unique = searched.iter().cloned().collect::<BTreeSet<_>>();
+            assert_eq!(searched.len(), unique.len(), "duplicate layouts for\n{board}");
+            assert_eq!(unique, brute_force(&board), "layouts differ for\n{board}");
         }
+    }
+
+    /// Boards the differential tests run on, from fully decided to needing a guess
+    const CORPUS: [&str; 5] = [
+        "?????\n?1?2?\n?2?3?\n?????",
+        EXAMPLE_BOARD,
+        "?21???\n?2123?\n11--??\n---???\n--????",
+        "111?1?\n??1-1?\n?21?3?\n?21???\n1??23?",
+        "--\n11\n??",
+    ];
 
-        if board.len() == 0 {
-            return Err("Empty input".to_string());
+    /// Runs static analysis if `analyse` is set and then the search the way `main` does, returning
+    /// the bombs of each possibility found
+    fn search(board: &Board, order: SearchOrder, max_frontier: Option<usize>, symmetry: bool, analyse: bool) -> Vec<Vec<(usize, usize)>> {
+        let mut board = board.clone();
+        if analyse {
+            board.apply_rules(&rules::default_rules());
         }
+        let ignore = board.unconstrained_cells();
+        let symmetries = if symmetry { board.symmetries() } else { Vec::new() };
 
-        let width = width.unwrap();
-        let height = board.len();
+        let mut frontier = Frontier::new(order, max_frontier);
+        let mut visited = HashSet::new();
+        let mut possibilities = Vec::new();
+        if board.is_solved() {
+            possibilities.push(board.clone());
+        }
+        if !board.is_solved() || board.has_slack() {
+            frontier.push(board);
+        }
+        while let Some(board) = frontier.pop() {
+            let (solved_boards, open_boards) = board.get_possible_boards(&mut visited, &ignore, &symmetries);
+            possibilities.extend(solved_boards.iter().flat_map(|board| board.orbit(&symmetries)));
+            open_boards.into_iter().for_each(|board| frontier.push(board));
+        }
 
-        Ok(Board {
-            board,
-            width,
-            height,
-        })
+        possibilities.iter()
+            .map(|board| (0..board.height).flat_map(|y| (0..board.width).map(move |x| (x, y)))
+                .filter(|&(x, y)| matches!(board.board[y][x], CellTypes::Bomb))
+                .collect_vec())
+            .collect()
     }
 
-    pub fn complete_solvable(&mut self) -> bool {
-        let mut change_made = false;
+    #[test]
+    fn static_analysis_agrees_with_the_search() {
+        let mut rules = rules::default_rules();
+        rules.push(Box::new(rules::TrialDeduction));
 
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let mut required = match &self.board[y][x] {
-                    CellTypes::Value(v) => *v,
-                    _ => continue,
-                } as i32;
-                if required == 0 { continue; }
+        for input in CORPUS {
+            let board = parse(input).unwrap();
+            let mut analysed = board.clone();
+            analysed.apply_rules(&rules);
+            assert!(self_check(&board, &analysed), "static analysis contradicts the search on\n{board}");
+        }
+    }
 
-                let mut possible_cells = Vec::new();
+    #[test]
+    fn search_options_find_the_same_possibilities() {
+        for input in CORPUS {
+            let board = parse(input).unwrap();
+            let expected = search(&board, SearchOrder::Bfs, None, false, true).into_iter().collect::<BTreeSet<_>>();
 
-                for offset in [(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1)] {
-                    let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
-                    if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
-                        continue;
-                    }
+            for (name, found) in [
+                ("default", search(&board, SearchOrder::Bfs, None, false, true)),
+                ("--no-static", search(&board, SearchOrder::Bfs, None, false, false)),
+                ("--symmetry", search(&board, SearchOrder::Bfs, None, true, true)),
+                ("--search best-first", search(&board, SearchOrder::BestFirst, None, false, true)),
+                ("--max-frontier 1", search(&board, SearchOrder::Bfs, Some(1), false, true)),
+            ] {
+                assert_eq!(found.len(), expected.len(), "{name} found a different number of possibilities for\n{board}");
+                assert_eq!(found.into_iter().collect::<BTreeSet<_>>(), expected, "{name} found different possibilities for\n{board}");
+            }
+        }
+    }
 
-                    let (x, y) = (x as usize, y as usize);
+    #[test]
+    fn best_guess_skips_flagged_cells() {
+        let board = parse("--\n11\nF?").unwrap();
+        let (mut result, _) = board.solve(&rules::default_rules());
+        result.use_strategy(&board, GuessStrategy::Safest);
 
-                    match &self.board[y][x] {
-                        CellTypes::Value(_) => continue,
-                        CellTypes::Bomb => required -= 1,
-                        CellTypes::Covered => possible_cells.push(offset),
-                    };
-                }
+        assert_eq!(result.best_guess.map(|(cell, _)| cell), Some((1, 2)));
+        assert_eq!(result.grid_string(), "--\n11\nF@");
+    }
 
-                if required == possible_cells.len() as i32 {
-                    for offset in possible_cells {
-                        let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
-                        let (x, y) = (x as usize, y as usize);
-                        self.board[y][x] = CellTypes::Bomb;
-                        change_made = true;
-                    }
-                }
-                else if required == 0 {
-                    for offset in possible_cells {
-                        let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
-                        let (x, y) = (x as usize, y as usize);
-                        self.board[y][x] = CellTypes::Value(0);
-                        change_made = true
-                    }
-                }
+    #[test]
+    fn best_guess_lands_on_a_covered_cell() {
+        for input in CORPUS {
+            let board = parse(input).unwrap();
+            let (result, _) = board.solve(&rules::default_rules());
+            if let Some(((x, y), _)) = result.best_guess {
+                assert!(board.board[y][x].is_covered(), "guess at [{x}, {y}] isn't covered in\n{board}");
+                assert_eq!(result.output[y][x], '@');
             }
         }
+    }
 
-        change_made
+    #[test]
+    fn no_guess_without_possibilities() {
+        let board = parse("?1?\n???").unwrap();
+        let result = Board::compile_guaranteed(&board, &[], &board.unconstrained_cells());
+        assert!(result.best_guess.is_none());
+        assert!(!result.grid_string().contains('@'));
     }
 
-    pub fn validate_board(&self) -> Result<usize, String> {
-        let mut to_satisfy = 0;
+    #[test]
+    fn more_flags_than_mines_is_rejected() {
+        let mut board = parse("F1F\n111\n???").unwrap();
+        board.mine_counts.push(MineCount { x: 0, y: 0, w: 3, h: 3, min: 1, max: 1 });
+        assert!(matches!(board.validate_board(), Err(SolverError::TooManyFlags { flagged: 2, declared: 1 })));
+    }
 
-        for x in 0..self.width {
-            for y in 0..self.height {
-                let mut required = match &self.board[y][x] {
-                    CellTypes::Value(v) => *v,
-                    _ => continue,
-                } as i32;
-                if required == 0 { continue; }
-
-                let mut possible_cells: u8 = 0;
-                for offset in [(-1, -1), (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1)] {
-                    let (x, y) = (x as i32 + offset.0, y as i32 + offset.1);
-                    if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
-                        continue;
-                    }
+    #[test]
+    fn more_bombs_than_mines_is_rejected() {
+        let mut board = parse("X?X\n???\n???").unwrap();
+        board.mine_counts.push(MineCount { x: 0, y: 0, w: 3, h: 3, min: 1, max: 1 });
+        assert!(matches!(board.validate_board(), Err(SolverError::TooManyMinesPlaced { placed: 2, declared: 1 })));
+    }
 
-                    let (x, y) = (x as usize, y as usize);
+    #[test]
+    fn solution_must_match_zeros() {
+        let board = parse("-?").unwrap();
+        assert!(matches!(board.verify_solution(&parse("-X").unwrap()), Err(SolverError::TooManyBombs { x: 0, y: 0, excess: 1 })));
+        assert!(board.verify_solution(&parse("--").unwrap()).is_ok());
+    }
 
-                    match &self.board[y][x] {
-                        CellTypes::Value(_) => continue,
-                        CellTypes::Bomb => required -= 1,
-                        CellTypes::Covered => possible_cells += 1
-                    };
-                }
+    /// The verdict `replay` gives the move from `before` to `after`
+    fn verdict(before: &str, after: &str) -> Verdict {
+        let (before, after) = (parse(before).unwrap(), parse(after).unwrap());
+        let revealed = before.diff(&after).unwrap().into_iter()
+            .filter(|(_, _, old, _)| old.is_covered())
+            .map(|(x, y, _, new)| (x, y, new))
+            .collect_vec();
+        let (result, _) = before.solve(&rules::default_rules());
+        judge_move(&before, &result, &revealed)
+    }
 
-                if required < 0 {
-                    return Err(format!("Cell at position [{}, {}] has {} bombs more than it should have", x, y, -required));
-                }
+    #[test]
+    fn replay_verdicts() {
+        // Only the middle of the right column can be a bomb
+        let column = "-1?\n-1?\n-1?";
+        assert_eq!(verdict(column, "-11\n-1?\n-1?"), Verdict::Safe);
+        assert_eq!(verdict(column, "-1?\n-12\n-1?"), Verdict::Mistake { forced: true });
 
-                if (possible_cells as i32) < required {
-                    return Err(format!("Cell at position [{}, {}] requires {} bomb(s) however only {} cell(s) can contain bombs", x, y, required, possible_cells));
-                }
+        let fifty_fifty = "--\n11\n??";
+        assert_eq!(verdict(fifty_fifty, "--\n11\n1?"), Verdict::Guess(Some(0.5)));
+        assert_eq!(verdict(fifty_fifty, "--\n11\nX?"), Verdict::Mistake { forced: false });
+    }
 
-                if required != 0 {
-                    to_satisfy += required as usize;
-                }
-            }
+    #[test]
+    fn cascades_are_judged_by_the_zero_clicked() {
+        // The 1 already has its bomb, so the cell after it is safe, and clicking it as a zero opens
+        // the next cell, which no number touches
+        assert_eq!(verdict("X1???", "X1--?"), Verdict::Safe);
+        assert_eq!(verdict("X1???", "X1?-?"), Verdict::Guess(None));
+    }
+
+    #[test]
+    fn fifty_fifty_is_found_by_every_solve() {
+        let (result, _) = parse("--\n11\n??").unwrap().solve(&rules::default_rules());
+        assert_eq!(result.fifty_fifty, Some(((0, 2), (1, 2))));
+
+        // Each cell is a bomb in a third of the possibilities
+        let (result, _) = parse("1?\n??").unwrap().solve(&rules::default_rules());
+        assert_eq!(result.fifty_fifty, None);
+    }
+
+    /// Linear congruential generator for the randomised tests, so they need nothing outside std
+    /// and see the same inputs every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            self.0 >> 33
         }
 
-        Ok(to_satisfy)
+        /// A number from 0 up to but not including `n`
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
     }
 
-    /// Solved, Open
-    pub fn get_possible_boards(&self, visited: &mut HashSet<u64>, ignore: &mut HashSet<(usize, usize)>) -> (Vec<Board>, Vec<Board>) {
-        let mut solved_boards = Vec::new();
-        let mut open_boards = Vec::new();
-        let current_satisfied = self.validate_board().unwrap();
+    #[test]
+    fn random_minefields_round_trip() {
+        let mut rng = Lcg(171);
+        for _ in 0..500 {
+            let (width, height) = (1 + rng.below(8), 1 + rng.below(8));
+            let mines = (0..height).map(|_| (0..width).map(|_| rng.below(5) == 0).collect_vec()).collect_vec();
+            let solution = Board::new(
+                mines.iter().map(|row| row.iter().map(|&mine| if mine { CellTypes::Bomb } else { CellTypes::Value(0) }).collect()).collect(),
+                Vec::new(),
+                Adjacency::default(),
+            );
 
-        for x in 0..self.width {
-            for y in 0..self.height {
-                if ignore.contains(&(x, y)) {
-                    continue
+            // Reveal some of the field, giving each revealed cell its real number, a range around
+            // it or no number at all
+            let hidden = (0..height).map(|_| (0..width).map(|_| rng.below(3) != 0).collect_vec()).collect_vec();
+            let mut cells = vec![vec![CellTypes::Covered; width]; height];
+            for y in 0..height {
+                for x in 0..width {
+                    let around = solution.neighbours(x, y).filter(|&(x, y)| mines[y][x]).count() as u8;
+                    cells[y][x] = match (mines[y][x], hidden[y][x], rng.below(6)) {
+                        (true, true, 0) => CellTypes::Flagged,
+                        (_, true, _) => CellTypes::Covered,
+                        (true, false, _) => CellTypes::Bomb,
+                        (false, false, 0) => CellTypes::UnknownValue,
+                        (false, false, 1) if around > 0 => CellTypes::ValueRange { min: around - 1, max: around },
+                        (false, false, _) => CellTypes::Value(around),
+                    };
                 }
+            }
+            let board = Board::new(cells, Vec::new(), Adjacency::default());
 
-                match &self.board[y][x] {
-                    CellTypes::Covered => {},
-                    _ => continue
-                }
+            let parsed = Board::from_string(board.to_string()).unwrap_or_else(|e| panic!("{e} parsing\n{board}"));
+            assert_eq!((parsed.width, parsed.height), (width, height));
+            assert_eq!(
+                parsed.board.iter().flatten().map(|cell| (cell.id(), cell.value_range())).collect_vec(),
+                board.board.iter().flatten().map(|cell| (cell.id(), cell.value_range())).collect_vec(),
+                "round trip changed\n{board}"
+            );
 
-                let mut new_board = self.clone();
-                new_board.board[y][x] = CellTypes::Bomb;
+            // Each number still needs the bombs planted around it that aren't shown
+            let expected = (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter_map(|(x, y)| board.board[y][x].value_range().map(|(min, _)| (x, y, min as usize)))
+                .map(|(x, y, min)| min.saturating_sub(board.neighbours(x, y).filter(|&(x, y)| matches!(board.board[y][x], CellTypes::Bomb)).count()))
+                .sum::<usize>();
+            assert_eq!(board.validate_board().unwrap_or_else(|e| panic!("{e} validating\n{board}")), expected, "for\n{board}");
+            assert!(board.verify_solution(&solution).is_ok(), "planted mines rejected for\n{board}");
+        }
+    }
 
-                let hash = new_board.get_hash();
-                if visited.contains(&hash) {
-                    continue;
-                }
-                visited.insert(hash);
+    #[test]
+    fn parsing_random_input_never_panics() {
+        // Pieces of valid boards, headers and legends as well as characters that don't belong
+        let pieces = [
+            "?", "X", "x", "-", "F", "~", "0", "1", "3", "8", "9", "(", ")", "(1-2)", "(2-", "(9-9)", " ", "\n", "\n\n", "\t",
+            "-1 ", "-2 ", "-3 ", "12 ", "#", "*", ".", "\u{e9}", "\u{2588}", "\u{0}",
+            "# minesweeper-board v1 mines=3 2x2\n", "# minesweeper-board v2\n", "legend: covered=# bomb=* val0=.\n", "legend: covered=\u{e9}\n",
+        ];
 
-                if let Ok(remaining) = new_board.validate_board() {
-                    if remaining == 0 {
-                        solved_boards.push(new_board);
-                        continue;
-                    }
-                    else if remaining == current_satisfied {
-                        ignore.insert((x, y));
-                        continue;
+        let mut rng = Lcg(170);
+        let inputs = (0..300).map(|i| if i % 2 == 0 {
+            String::from_utf8_lossy(&(0..rng.below(40)).map(|_| rng.next() as u8).collect_vec()).into_owned()
+        } else {
+            (0..rng.below(30)).map(|_| pieces[rng.below(pieces.len())]).collect()
+        }).collect_vec();
+
+        for input in &inputs {
+            let legend = Legend::detect(input).unwrap_or_default();
+            for adjacency in [Adjacency::King, Adjacency::Orthogonal, Adjacency::Diagonal] {
+                for flags in 0..16 {
+                    let options = ParseOptions {
+                        legend,
+                        lenient: flags & 1 != 0,
+                        ints: flags & 2 != 0,
+                        ragged: flags & 4 != 0,
+                        adjacency,
+                        clamp: flags & 8 != 0,
+                    };
+                    if let Ok((board, _)) = Board::from_string_with(input.clone(), &options) {
+                        assert_eq!(board.to_string().lines().count(), board.height, "for {input:?}");
                     }
                 }
-                else { continue; }
-                open_boards.push(new_board);
             }
         }
+    }
 
-        (solved_boards, open_boards)
+    #[test]
+    fn covered_and_flagged_boards_have_no_information() {
+        assert!(parse("???\n???").unwrap().has_no_information());
+        assert!(parse("FF\nFF").unwrap().has_no_information());
+        assert!(!parse("???\n?1?").unwrap().has_no_information());
+
+        let mut counted = parse("???\n???").unwrap();
+        counted.mine_counts.push(MineCount { x: 0, y: 0, w: 3, h: 2, min: 1, max: 1 });
+        assert!(!counted.has_no_information());
     }
 
-    pub fn get_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
+    #[test]
+    fn too_few_cells_and_no_cells_left_are_told_apart() {
+        let error = parse("3?\n?-").unwrap().validate_board().unwrap_err();
+        assert!(matches!(error, SolverError::NotEnoughCells { x: 0, y: 0, required: 3, available: 2 }));
+        assert_eq!(error.to_string(), "Cell at position [0, 0] requires 3 bomb(s) however only 2 cell(s) can contain bombs");
 
-        for line in &self.board {
-            for cell in line {
-                cell.id().hash(&mut hasher);
-            }
-        }
+        let error = parse("2X\n--").unwrap().validate_board().unwrap_err();
+        assert!(matches!(error, SolverError::NeighboursDetermined { x: 0, y: 0, required: 1 }));
+        assert_eq!(error.to_string(), "Cell at position [0, 0] requires 1 more bomb(s) but all of its neighbours are already revealed or bombs");
+    }
 
-        hasher.finish()
+    /// `input` with a mine count of `mines` over the whole board, as --mines gives
+    fn with_total(input: &str, mines: usize) -> Board {
+        let mut board = parse(input).unwrap();
+        board.mine_counts.push(MineCount { x: 0, y: 0, w: board.width, h: board.height, min: mines, max: mines });
+        board
     }
 
-    pub fn to_string(&self) -> String {
-        let mut output = String::new();
+    #[test]
+    fn endgame_with_a_mine_in_every_covered_cell() {
+        let mut board = with_total("X?\n?F", 4);
+        assert!(rules::MineCountEndgame.apply(&mut board));
+        assert_eq!(board.to_string(), "xx\nxx");
+    }
 
-        for line in &self.board {
-            for cell in line {
-                output.push(cell.char());
-            }
-            output.push('\n');
-        }
+    #[test]
+    fn endgame_with_no_mines_left() {
+        let mut board = with_total("X?\n?F", 1);
+        assert!(rules::MineCountEndgame.apply(&mut board));
+        assert_eq!(board.to_string(), "x-\n--");
+    }
 
-        output.remove(output.len() - 1);
+    #[test]
+    fn degenerate_boards_are_formatted() {
+        assert_eq!(Board::new(Vec::new(), Vec::new(), Adjacency::default()).to_string(), "");
 
-        output
+        let cells = vec![CellTypes::Covered, CellTypes::Value(1), CellTypes::Bomb, CellTypes::ValueRange { min: 0, max: 1 }];
+        assert_eq!(Board::new(vec![cells.clone()], Vec::new(), Adjacency::default()).to_string(), "?1x(0-1)");
+        let column = cells.into_iter().map(|cell| vec![cell]).collect();
+        assert_eq!(Board::new(column, Vec::new(), Adjacency::default()).to_string(), "?\n1\nx\n(0-1)");
     }
-}
 
-impl Display for Board {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.to_string())
+    #[test]
+    fn reasons_list_cells_row_by_row() {
+        // Neighbours are found column by column, so these bombs are found in a different order
+        let satisfied = parse("X?X\n?3?\nX??").unwrap().reason(1, 0, CellTypes::Value(0));
+        assert_eq!(satisfied.describe("count satisfied"), "forced by the number at [1, 1] whose 3 mine(s) are satisfied by [0, 0], [2, 0], [0, 2]");
+        assert_eq!(
+            satisfied.to_json().to_json(),
+            "{\"reason\":\"satisfied\",\"numbers\":[{\"x\":1,\"y\":1}],\"mines\":[{\"x\":0,\"y\":0},{\"x\":2,\"y\":0},{\"x\":0,\"y\":2}]}"
+        );
+
+        let filled = parse("X-?\n-3-\n?--").unwrap().reason(2, 0, CellTypes::Bomb);
+        assert_eq!(filled.describe("all remaining bombs"), "forced by the number at [1, 1] whose 3 mine(s) can only be [0, 0], [2, 0], [0, 2]");
+    }
+
+    #[test]
+    fn ranges_merge_with_compatible_cells() {
+        // Each board's second row gives the number on the left room for up to 3 bombs
+        let merge = |first: &str, second: &str| {
+            let parse = |input: &str| parse(&format!("{input}\n??")).unwrap();
+            parse(first).merge(&parse(second)).map(|board| board.to_string().lines().next().unwrap().to_string())
+        };
+        assert_eq!(merge("~?", "(1-2)?").unwrap(), "(1-2)?");
+        assert_eq!(merge("(1-2)?", "1?").unwrap(), "1?");
+        assert_eq!(merge("(0-2)?", "(1-3)?").unwrap(), "(1-2)?");
+        assert_eq!(merge("(0-1)?", "(1-3)?").unwrap(), "1?");
+        assert!(matches!(merge("(1-2)?", "3?"), Err(SolverError::MergeConflict { x: 0, y: 0, .. })));
+        assert!(matches!(merge("(0-1)?", "(2-3)?"), Err(SolverError::MergeConflict { x: 0, y: 0, .. })));
     }
 }
\ No newline at end of file
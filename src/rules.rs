@@ -0,0 +1,211 @@
+//! Deduction rules used by static analysis
+
+use crate::{Board, CellTypes};
+
+/// A single deduction technique that places bombs or reveals safe cells it can prove
+pub trait DeductionRule {
+    /// Applies the rule once over the whole board, returning whether anything changed
+    fn apply(&self, board: &mut Board) -> bool;
+
+    /// Short description of the rule for listing the deductions it makes
+    fn name(&self) -> &'static str;
+}
+
+/// The rules static analysis runs by default
+pub fn default_rules() -> Vec<Box<dyn DeductionRule>> {
+    vec![
+        Box::new(CountSatisfied),
+        Box::new(AllRemainingBombs),
+        Box::new(SubsetElimination),
+        Box::new(MineCountEndgame),
+    ]
+}
+
+fn mark(board: &mut Board, cells: &[(usize, usize)], cell: CellTypes) -> bool {
+    for &(x, y) in cells {
+        board.board[y][x] = cell;
+    }
+    !cells.is_empty()
+}
+
+/// A number with all of its bombs placed makes its other covered neighbours safe
+pub struct CountSatisfied;
+
+impl DeductionRule for CountSatisfied {
+    fn apply(&self, board: &mut Board) -> bool {
+        let mut change_made = false;
+
+        for y in 0..board.height {
+            for x in 0..board.width {
+                if let Some((0, covered)) = board.constraint(x, y) {
+                    change_made |= mark(board, &covered, CellTypes::Value(0));
+                }
+            }
+        }
+
+        change_made
+    }
+
+    fn name(&self) -> &'static str {
+        "count satisfied"
+    }
+}
+
+/// A number needing as many bombs as it has covered neighbours makes them all bombs
+pub struct AllRemainingBombs;
+
+impl DeductionRule for AllRemainingBombs {
+    fn apply(&self, board: &mut Board) -> bool {
+        let mut change_made = false;
+
+        for y in 0..board.height {
+            for x in 0..board.width {
+                if let Some((required, covered)) = board.constraint(x, y) {
+                    if required > 0 && required as usize == covered.len() {
+                        change_made |= mark(board, &covered, CellTypes::Bomb);
+                    }
+                }
+            }
+        }
+
+        change_made
+    }
+
+    fn name(&self) -> &'static str {
+        "all remaining bombs"
+    }
+}
+
+/// If one number's covered neighbours are a subset of another's, the cells only the larger one
+/// touches must hold the difference in their required bombs
+pub struct SubsetElimination;
+
+impl DeductionRule for SubsetElimination {
+    fn apply(&self, board: &mut Board) -> bool {
+        let mut change_made = false;
+
+        for y in 0..board.height {
+            for x in 0..board.width {
+                let Some((required, covered)) = board.constraint(x, y) else { continue; };
+                if covered.is_empty() {
+                    continue;
+                }
+
+                // Only numbers within two cells can share covered neighbours
+                for (ox, oy) in board.neighbours(x, y).flat_map(|(x, y)| board.neighbours(x, y)).collect::<Vec<_>>() {
+                    if (ox, oy) == (x, y) {
+                        continue;
+                    }
+                    let Some((other_required, other_covered)) = board.constraint(ox, oy) else { continue; };
+                    if !covered.iter().all(|cell| other_covered.contains(cell)) {
+                        continue;
+                    }
+
+                    let difference = other_covered.iter().filter(|cell| !covered.contains(cell)).copied().collect::<Vec<_>>();
+                    if difference.is_empty() {
+                        continue;
+                    }
+
+                    let needed = other_required - required;
+                    if needed == 0 {
+                        change_made |= mark(board, &difference, CellTypes::Value(0));
+                    }
+                    else if needed as usize == difference.len() {
+                        change_made |= mark(board, &difference, CellTypes::Bomb);
+                    }
+                }
+            }
+        }
+
+        change_made
+    }
+
+    fn name(&self) -> &'static str {
+        "subset elimination"
+    }
+}
+
+/// For variants where every mine touches a number - a covered cell with no numbered neighbour is
+/// safe. Not part of the default rules as it's wrong for normal boards.
+pub struct MinesTouchNumbers;
+
+impl DeductionRule for MinesTouchNumbers {
+    fn apply(&self, board: &mut Board) -> bool {
+        let untouched = board.covered_cells()
+            .filter(|&(x, y)| !board.neighbours(x, y).any(|(x, y)| matches!(board.board[y][x].value_range(), Some((_, max)) if max > 0)))
+            .collect::<Vec<_>>();
+
+        mark(board, &untouched, CellTypes::Value(0))
+    }
+
+    fn name(&self) -> &'static str {
+        "mines touch numbers"
+    }
+}
+
+/// A mine count with no room for more bombs makes its covered cells safe, and one needing at least
+/// as many bombs as it has covered cells makes them all bombs. With `--mines` this is the endgame where the
+/// remaining mines fill, or are missing from, every covered cell.
+pub struct MineCountEndgame;
+
+impl DeductionRule for MineCountEndgame {
+    fn apply(&self, board: &mut Board) -> bool {
+        let mut change_made = false;
+
+        for i in 0..board.mine_counts.len() {
+            let mine_count = &board.mine_counts[i];
+            let (mut bombs, mut covered) = (0, Vec::new());
+            for y in mine_count.y..mine_count.y + mine_count.h {
+                for x in mine_count.x..mine_count.x + mine_count.w {
+                    match board.board[y][x] {
+                        CellTypes::Bomb => bombs += 1,
+                        CellTypes::Covered | CellTypes::Flagged => covered.push((x, y)),
+                        _ => {}
+                    }
+                }
+            }
+
+            if bombs == mine_count.max {
+                change_made |= mark(board, &covered, CellTypes::Value(0));
+            }
+            else if mine_count.min > bombs && mine_count.min - bombs == covered.len() {
+                change_made |= mark(board, &covered, CellTypes::Bomb);
+            }
+        }
+
+        change_made
+    }
+
+    fn name(&self) -> &'static str {
+        "mine count endgame"
+    }
+}
+
+/// Assumes each covered cell a number touches is a bomb, then safe, running the default rules
+/// after each. An assumption that contradicts the numbers proves the cell is the other one. Much
+/// slower than the other rules - each pass runs static analysis twice per cell - but still far
+/// cheaper than the full search. Not part of the default rules.
+pub struct TrialDeduction;
+
+impl DeductionRule for TrialDeduction {
+    fn apply(&self, board: &mut Board) -> bool {
+        let rules = default_rules();
+        let mut change_made = false;
+
+        let unconstrained = board.unconstrained_cells();
+        for (x, y) in board.covered_cells().filter(|cell| !unconstrained.contains(cell)).collect::<Vec<_>>() {
+            if board.assume_bomb(x, y, &rules).is_err() {
+                change_made |= mark(board, &[(x, y)], CellTypes::Value(0));
+            }
+            else if board.assume_safe(x, y, &rules).is_err() {
+                change_made |= mark(board, &[(x, y)], CellTypes::Bomb);
+            }
+        }
+
+        change_made
+    }
+
+    fn name(&self) -> &'static str {
+        "trial deduction"
+    }
+}
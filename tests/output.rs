@@ -0,0 +1,166 @@
+//! Checks the full output of the binary for a few boards in each output mode, so format changes
+//! are never accidental.
+//!
+//! When a change to the output is intended, run the binary on the same board with the same
+//! arguments and paste what it prints over the expected string, then check the diff of this file
+//! reads as the change you meant.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const GUARANTEED: &str = "?21???\n?2123?\n11--??\n---???\n--????";
+const FIFTY_FIFTY: &str = "--\n11\n??";
+
+/// Pipes `board` into the binary with `args` and returns what it printed
+fn run(board: &str, args: &[&str]) -> String {
+    String::from_utf8(run_output(board, args).stdout).unwrap()
+}
+
+/// Pipes `board` into the binary with `args` and returns how it exited along with what it printed to
+/// stdout and stderr
+fn run_output(board: &str, args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minesweeper-solver"))
+        .args(args)
+        .env_remove("MINESWEEPER_BOARD")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(board.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn guaranteed_cells() {
+    assert_eq!(run(GUARANTEED, &[]), "Input:
+?21???
+?2123?
+11--??
+---???
+--????
+
+After static analysis:
+x21x??
+x2123?
+11--??
+---???
+--????
+
+Finished finding solutions - 6 possibilities
+
+Between 5 and 12 mines in total
+
+Guaranteed cells:
+#21#??
+#2123?
+11--??
+---???
+--????
+
+Found 3 guaranteed bomb(s), 0 guaranteed safe cell(s)
+
+Key:
+\t'#' - Guaranteed bomb
+\t'O' - Guaranteed safe
+
+Use the -s argument to show individual board possibilities
+");
+}
+
+#[test]
+fn show_possibilities() {
+    assert_eq!(run(FIFTY_FIFTY, &["-s"]), "Input:
+--
+11
+??
+
+After static analysis:
+--
+11
+??
+
+Possible board found:
+--
+11
+x?
+
+Possible board found:
+--
+11
+?x
+
+Finished finding solutions - 2 possibilities
+
+1 mine(s) in total
+
+--
+11
+@?
+
+Unavoidable 50/50 between (0,2) and (1,2) - Exactly one of them is a bomb and no cell is safer to reveal
+2 cell(s) can only be decided by guessing: [0, 2], [1, 2]
+
+");
+}
+
+#[test]
+fn json() {
+    assert_eq!(run(FIFTY_FIFTY, &["--json"]), "{\"input\":{\"board\":\"--\\n11\\n??\",\"width\":2,\"height\":3},\"solved\":false,\"possibilities\":2,\"guaranteed\":\"--\\n11\\n@?\",\"bombs\":0,\"safe\":0,\"best_guess\":{\"x\":0,\"y\":2,\"safe_probability\":0.5},\"undetermined\":[{\"x\":0,\"y\":2},{\"x\":1,\"y\":2}],\"fifty_fifty\":[{\"x\":0,\"y\":2},{\"x\":1,\"y\":2}],\"cells\":[{\"x\":0,\"y\":2,\"status\":\"ambiguous\",\"bomb_probability\":0.5},{\"x\":1,\"y\":2,\"status\":\"ambiguous\",\"bomb_probability\":0.5}],\"numbers\":[{\"x\":0,\"y\":0,\"value\":0},{\"x\":1,\"y\":0,\"value\":0},{\"x\":0,\"y\":1,\"value\":1},{\"x\":1,\"y\":1,\"value\":1}],\"exact\":true,\"bomb_count\":{\"min\":1,\"max\":1}}
+");
+}
+
+#[test]
+fn solved_deterministically() {
+    let example = run("", &["--example"]);
+    assert_eq!(run(&example, &[]), "Input:
+?x?-1??-
+111-1F1-
+-1111221
+-1??-1??
+
+Solved deterministically:
+-x--1---
+111-1x1-
+-1111221
+-1x--1x-
+");
+}
+
+#[test]
+fn move_only_fails_silently_without_a_move() {
+    for (name, board) in [("all covered", "??\n??"), ("unparsable", "3Z"), ("invalid", "2-\n--")] {
+        let output = run_output(board, &["--move-only"]);
+        assert!(!output.status.success(), "{name} board exited successfully");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "", "{name} board printed to stdout");
+    }
+
+    let output = run_output(GUARANTEED, &["--move-only"]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn unsupported_bombs_warn_unless_strict() {
+    let board = "X-\n--\n11\n??";
+    let output = run_output(board, &["--actions"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "Warning: Bomb at position [0, 0] has no numbered neighbour to confirm it\n");
+
+    let output = run_output(board, &["--actions", "--strict-bombs"]);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Invalid board:\n\tBomb at position [0, 0] has no numbered neighbour to confirm it\n");
+}
+
+#[test]
+fn explain_guess_without_a_move() {
+    let output = run("2?\n?2", &["--no-static", "--explain-guess"]);
+    assert!(output.contains("\nNo cell to recommend - every covered cell is a guaranteed bomb\n"), "{output}");
+}
+
+#[test]
+fn mines_touch_numbers_without_static_analysis() {
+    let board = "1?\n??\n??";
+    let analysed = run(board, &["--list-covered", "--mines-touch-numbers"]);
+    assert!(analysed.contains("0 2 safe"), "{analysed}");
+    assert_eq!(run(board, &["--list-covered", "--mines-touch-numbers", "--no-static"]), analysed);
+}